@@ -0,0 +1,21 @@
+fn parse(input: &str) -> Result<Vec<u32>> {
+    let mut numbers = vec![];
+    for line in input.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue
+        }
+        numbers.push(line.parse()?);
+    }
+    Ok(numbers)
+}
+
+fn total(numbers: &[u32]) -> u32 {
+    numbers.iter().sum()
+}
+
+fn main() {
+    let input = std::fs::read_to_string("input.txt").unwrap();
+    let numbers = parse(&input).unwrap();
+    println!("{}", total(&numbers));
+}
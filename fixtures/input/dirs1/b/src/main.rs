@@ -0,0 +1,22 @@
+fn parse(input: &str) -> Result<Vec<u64>> {
+    let mut numbers = vec![];
+    for line in input.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue
+        }
+        numbers.push(line.parse()?);
+    }
+    Ok(numbers)
+}
+
+fn total(numbers: &[u64]) -> u64 {
+    numbers.iter().sum()
+}
+
+fn main() -> Result<()> {
+    let input = std::fs::read_to_string("input.txt")?;
+    let numbers = parse(&input)?;
+    println!("total = {}", total(&numbers));
+    Ok(())
+}
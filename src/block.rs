@@ -1,10 +1,129 @@
+#[cfg(feature = "cli")]
 use std::io::{BufWriter, Write};
 use std::cmp::{min};
+#[cfg(feature = "cli")]
+use bstr::ByteSlice;
+#[cfg(feature = "cli")]
+use unicode_width::UnicodeWidthStr;
+#[cfg(feature = "cli")]
 use anyhow::{Result};
 use super::part::Part;
+#[cfg(feature = "cli")]
 use super::style;
 use super::types::*;
+#[cfg(feature = "cli")]
 use super::whitespace::CheckAllWhitespace;
+#[cfg(feature = "cli")]
+use super::syntax::{Class, Highlighter};
+#[cfg(feature = "cli")]
+use super::char_differ::{self, word_pieces};
+#[cfg(feature = "cli")]
+use super::word_differ;
+#[cfg(feature = "cli")]
+use super::invisibles::{self, Kind, Show};
+
+#[cfg(feature = "cli")]
+const TAB_WIDTH: usize = 8;
+
+// (highlight, syntax highlight, trailing whitespace, text)
+#[cfg(feature = "cli")]
+type Segment<'a> = (&'a [u8], &'a [u8], bool, &'a [u8]);
+
+// a wrapped line and its width
+#[cfg(feature = "cli")]
+type WrappedLine<'a> = (Vec<(&'a [u8], &'a [u8], bool, Vec<u8>)>, usize);
+
+// one line on one side of the side by side view
+#[cfg(feature = "cli")]
+struct Row<'a> {
+    lineno: usize,
+    segments: Vec<Segment<'a>>,
+}
+
+#[cfg(feature = "cli")]
+fn wrap_segments<'a>(segments: &[Segment<'a>], width: usize) -> Vec<WrappedLine<'a>> {
+    let mut lines = vec![(vec![], 0)];
+
+    for &(highlight, syntax, trailing_ws, text) in segments {
+        let mut new_segment = true;
+        for (start, end, g) in text.grapheme_indices() {
+            let col = lines.last().unwrap().1;
+            let cols = if g == "\t" { TAB_WIDTH - col % TAB_WIDTH } else { g.width() };
+
+            if col > 0 && col + cols > width {
+                lines.push((vec![], 0));
+                new_segment = true;
+            }
+            let (line, col) = lines.last_mut().unwrap();
+            let cols = cols.min(width - *col);
+            if new_segment {
+                line.push((highlight, syntax, trailing_ws, vec![]));
+                new_segment = false;
+            }
+
+            let buf = &mut line.last_mut().unwrap().3;
+            if g == "\t" {
+                buf.resize(buf.len() + cols, b' ');
+            } else {
+                buf.extend_from_slice(&text[start..end]);
+            }
+            *col += cols;
+        }
+    }
+
+    lines
+}
+
+// the length in bytes of the first grapheme of a word
+#[cfg(feature = "cli")]
+fn first_grapheme(word: &[u8]) -> usize {
+    word.grapheme_indices().next().map_or(word.len(), |(_, end, _)| end)
+}
+
+// writes a word in its syntax colour, then goes back to the highlight
+#[cfg(feature = "cli")]
+fn write_word<T: Write>(
+    stdout: &mut BufWriter<T>,
+    word: &[u8],
+    class: Option<Class>,
+    highlight: &[u8],
+    show: Option<Show>,
+    style_opts: &super::style::StyleOpts,
+) -> Result<()> {
+    if let Some(class) = class && !word.is_empty() {
+        let syntax = style_opts.syntax(class).as_bytes();
+        stdout.write_all(syntax)?;
+        stdout.write_all(&invisibles::replace(word, show, style_opts, &[highlight, syntax]))?;
+        stdout.write_all(highlight)?;
+    } else {
+        stdout.write_all(&invisibles::replace(word, show, style_opts, &[highlight]))?;
+    }
+    Ok(())
+}
+
+// whether to print a non matching part as if it matched
+#[cfg(feature = "cli")]
+fn ignored(part: &Part, style: style::Style) -> bool {
+    style.ignore_space.is_some_and(|ignore| word_differ::ignorable(part, ignore))
+}
+
+// the number of leading words that are indentation, and the column they reach
+#[cfg(feature = "cli")]
+fn indentation(line: &[Bytes]) -> (usize, usize) {
+    let words = line.iter().take_while(|w| **w != b"\n" && w.is_ascii_whitespace());
+    words.fold((0, 0), |(n, col), w| {
+        (n + 1, w.iter().fold(col, |col, &c| if c == b'\t' { col + TAB_WIDTH - col % TAB_WIDTH } else { col + 1 }))
+    })
+}
+
+// the two sides of a block split into lines
+#[cfg(feature = "cli")]
+fn side_lines<'a>(block: &'a Block, i: usize) -> Vec<Vec<Bytes<'a>>> {
+    let words: Vec<_> = block.parts.iter().flat_map(|p| p.get(i)).copied().collect();
+    let mut lines: Vec<Vec<_>> = words.split_inclusive(|w| *w == b"\n").map(|l| l.to_vec()).collect();
+    lines.retain(|l| !l.is_empty());
+    lines
+}
 
 fn find_common_prefix_length(a: &[Bytes], b: &[Bytes]) -> usize {
     a.iter().zip(b).take_while(|(a, b)| a == b).count()
@@ -29,7 +148,7 @@ impl Block<'_> {
         self.parts.iter().all(|p| p.matches && p.whole_line())
     }
 
-    fn score(&self) -> f64 {
+    pub fn score(&self) -> f64 {
         // limit the effect of very long blocks
         const MAXLEN: usize = 10;
         let total: usize = self.parts.iter().map(|p| min(MAXLEN, p.word_len(0)) + min(MAXLEN, p.word_len(1))).sum();
@@ -181,13 +300,10 @@ impl Block<'_> {
         self.parts.iter().rev().find(|p| !p.is_empty(i))
     }
 
-    pub fn split_block(mut self) -> Vec<Self> {
-        self.squeeze_parts();
-        super::shift::shift_parts(&mut self.parts);
-
+    // group parts based on line numbers
+    fn split_lines(self) -> Vec<Self> {
         let mut blocks = vec![Block{parts: vec![]}];
 
-        // group parts based on line numbers
         for part in self.parts {
             if part.is_empty(0) && part.is_empty(1) {
                 continue
@@ -204,6 +320,98 @@ impl Block<'_> {
             }
             blocks.last_mut().unwrap().parts.push(part);
         }
+        blocks
+    }
+
+    // whether the only changes are to the whitespace at the start of lines,
+    // and the new indentation is wide enough to print the change over it
+    #[cfg(feature = "cli")]
+    fn reindented(&self) -> bool {
+        let [left, right] = [0, 1].map(|i| side_lines(self, i));
+        let fits = left.iter().zip(right.iter()).all(|(left, right)| {
+            let (_, new) = indentation(right);
+            let delta = new as isize - indentation(left).1 as isize;
+            format!("{:+}", delta).len() < new
+        });
+        if !fits {
+            return false
+        }
+
+        let mut changed = false;
+        for part in self.parts.iter().filter(|p| !p.matches) {
+            for i in 0..=1 {
+                let line_start = part.parent.get_wordno(i, part.first_lineno(i));
+                let words = &part.parent.words[i][line_start .. part.slices[i].end];
+                if words.iter().any(|w| *w == b"\n" || !w.is_ascii_whitespace()) {
+                    return false
+                }
+            }
+            changed = true;
+        }
+        changed
+    }
+
+    // whether every part matches, or would if its whitespace was ignored
+    #[cfg(feature = "cli")]
+    fn matches(&self, style: style::Style) -> bool {
+        self.parts.iter().all(|p| p.matches || ignored(p, style) || (p.is_empty(0) && p.is_empty(1)))
+    }
+
+    // splits matching parts after each newline, so that every line can be put in a block of its own
+    #[cfg(feature = "cli")]
+    fn split_matching_lines(&mut self) {
+        let mut parts = vec![];
+        for mut part in self.parts.drain(..) {
+            if part.matches {
+                while let Some(n) = part.get(0).iter().position(|w| *w == b"\n")
+                    && n + 1 < part.get(0).len()
+                    && part.get(1).get(n).is_some_and(|w| *w == b"\n")
+                {
+                    let (first, rest) = part.partition(part.slices[0].start + n + 1, part.slices[1].start + n + 1, true);
+                    parts.push(first);
+                    part = rest;
+                }
+            }
+            parts.push(part);
+        }
+        self.parts = parts;
+    }
+
+    // splits off runs of lines where f is true into blocks of their own
+    #[cfg(feature = "cli")]
+    fn split_lines_where<F: Fn(&Self) -> bool>(mut self, f: F) -> Vec<Self> {
+        self.split_matching_lines();
+        let mut blocks: Vec<(Self, bool)> = vec![];
+        for line in self.split_lines() {
+            let split = f(&line);
+            match blocks.last_mut() {
+                Some((block, s)) if *s == split => block.parts.extend(line.parts),
+                _ => blocks.push((line, split)),
+            }
+        }
+        blocks.into_iter().map(|(mut block, _)| {
+            block.merge_adjacent_parts();
+            block
+        }).collect()
+    }
+
+    // splits off the lines that only changed their indentation into blocks of their own
+    #[cfg(feature = "cli")]
+    pub fn split_reindented(self) -> Vec<Self> {
+        self.split_lines_where(Self::reindented)
+    }
+
+    // splits off the lines that only changed whitespace that is being ignored
+    #[cfg(feature = "cli")]
+    pub fn split_ignored(self, style: style::Style) -> Vec<Self> {
+        self.split_lines_where(|block| block.matches(style))
+    }
+
+    pub fn split_block(mut self) -> Vec<Self> {
+        self.squeeze_parts();
+        super::shift::shift_parts(&mut self.parts);
+
+        let mut blocks = self.split_lines();
 
         // match leading whitespace in each block
         // since it got treated as junk during the diff
@@ -293,6 +501,226 @@ impl Block<'_> {
 
         blocks
     }
+}
+
+#[cfg(feature = "cli")]
+impl Block<'_> {
+    fn print_side_by_side<T: Write>(
+        &self,
+        stdout: &mut BufWriter<T>,
+        merge_markers: Option<&super::hunk::MergeMarkers>,
+        style: style::Style,
+        style_opts: &super::style::StyleOpts,
+        last: bool,
+        width: usize,
+    ) -> Result<()> {
+
+        let context = !style.show_both && self.matches(style);
+
+        let mut rows = [vec![], vec![]];
+        // rows where each matching part starts a line
+        let mut anchors = [vec![], vec![]];
+
+        for i in 0..=1 {
+            let rows = &mut rows[i];
+            let mut lineno = self.parts[0].first_lineno(i);
+            let mut newline = true;
+            let mut syntax = style.syntax.map(Highlighter::new);
+
+            for (n, part) in self.parts.iter().enumerate() {
+                if part.is_empty(i) {
+                    continue
+                }
+
+                let highlight = if ignored(part, style) {
+                    style_opts.diff_ignored_space.as_bytes()
+                } else if context {
+                    style_opts.diff_context.as_bytes()
+                } else if part.matches {
+                    style.diff_matching[i]
+                } else {
+                    style.diff_non_matching[i]
+                };
+
+                if newline && part.matches {
+                    anchors[i].push((n, rows.len()));
+                }
+
+                let pieces = if style.char_diff { char_differ::diff_part(part) } else { None };
+                let mut offset = 0;
+                let words = part.get(i);
+                let last = words.len() - 1;
+                for (j, word) in words.iter().enumerate() {
+                    let word_offset = offset;
+                    offset += word.len();
+                    if newline {
+                        rows.push(Row{lineno, segments: vec![]});
+                        newline = false;
+                    }
+                    let class = syntax.as_mut().and_then(|h| h.next(word, words.get(j+1).map(|w| w.as_bytes())));
+                    if *word == b"\n" {
+                        lineno += 1;
+                        newline = true;
+                        continue
+                    }
+                    let trailing_ws = words[last] == b"\n" && words[j..last].iter().all(|&w| w.is_ascii_whitespace());
+                    let syntax_highlight = class.map(|c| style_opts.syntax(c).as_bytes()).unwrap_or(b"");
+                    for (piece, changed) in word_pieces(word, word_offset, pieces.as_ref().map(|p| &p[i])) {
+                        let highlight = if changed { highlight } else { style.diff_matching[i] };
+                        for (text, kind) in invisibles::split(piece, Show::new(style.show_invisibles, !context)) {
+                            let segment = if kind == Kind::Text {
+                                (highlight, syntax_highlight, trailing_ws, text)
+                            } else {
+                                // the glyph style goes in place of the syntax colour
+                                (highlight, kind.style(style_opts).as_bytes(), false, text)
+                            };
+                            rows.last_mut().unwrap().segments.push(segment);
+                        }
+                    }
+                }
+            }
+
+            // print the no newline message
+            if last
+            && let Some(part) = self.parts.iter().rev().find(|p| !p.is_empty(i))
+            && !part.get(i).ends_with(&[b"\n".into()]) {
+                let highlight = if context { style_opts.diff_context.as_bytes() } else { style.diff_non_matching[i] };
+                rows.push(Row{lineno: 0, segments: vec![(highlight, b"", false, b"\\ No newline at end of file")]});
+            }
+        }
+
+        // pair up the rows, lining up the matching parts
+        let mut pairs = vec![];
+        let mut next = [0, 0];
+        let anchors = anchors[0].iter().filter_map(|(n, l)| anchors[1].iter().find(|(m, _)| m == n).map(|(_, r)| [*l, *r]));
+        for anchor in anchors.chain(std::iter::once([rows[0].len(), rows[1].len()])) {
+            while next[0] < anchor[0] || next[1] < anchor[1] {
+                pairs.push([0, 1].map(|i| (next[i] < anchor[i]).then(|| { next[i] += 1; &rows[i][next[i] - 1] })));
+            }
+        }
+
+        let sign_width = if style.signs { 1 } else { 0 };
+        let gutter_width = if style.line_numbers { style.lineno_width + 2 } else { 0 } + sign_width;
+        let column_width = (width.saturating_sub(gutter_width * 2 + 1) / 2).max(1);
+
+        for pair in pairs {
+            let wrapped = pair.map(|row| row.map(|row| wrap_segments(&row.segments, column_width)).unwrap_or_default());
+            let height = wrapped[0].len().max(wrapped[1].len());
+
+            for line in 0..height {
+                for i in 0..=1 {
+                    if i == 1 {
+                        stdout.write_all(b" ")?;
+                    }
+
+                    let row = pair[i];
+                    let base = match row {
+                        Some(_) if context => style_opts.diff_context.as_bytes(),
+                        Some(_) => style.diff_matching[i],
+                        None => style::RESET,
+                    };
+
+                    if style.line_numbers {
+                        let lineno = row.filter(|_| line == 0).map(|r| r.lineno).unwrap_or(0);
+                        let lineno_style = if context { &style_opts.lineno } else if i == 0 { &style_opts.lineno_left } else { &style_opts.lineno_right };
+                        let bar_style = merge_markers.and_then(|m| m.get(&(i, lineno)).map(|x| x.as_ref())).unwrap_or(&*style_opts.lineno_bar);
+                        stdout.write_all(style::format_side_lineno(lineno, lineno_style, bar_style, style.lineno_width, style.hyperlink.filter(|_| i == 1)).as_bytes())?;
+                    }
+                    if style.signs {
+                        stdout.write_all(if row.is_some() && !context && line == 0 { style::SIGN[i] } else { style::SIGN[2] })?;
+                    }
+
+                    stdout.write_all(base)?;
+                    let mut col = 0;
+                    if let Some((segments, width)) = wrapped[i].get(line) {
+                        for (highlight, syntax, trailing_ws, text) in segments {
+                            stdout.write_all(highlight)?;
+                            stdout.write_all(syntax)?;
+                            if *trailing_ws {
+                                stdout.write_all(style_opts.diff_trailing_ws.as_bytes())?;
+                            }
+                            stdout.write_all(text)?;
+                        }
+                        stdout.write_all(base)?;
+                        col = *width;
+                    }
+
+                    // pad out the left column
+                    if i == 0 {
+                        stdout.write_all(&b" ".repeat(column_width - col))?;
+                    }
+                    stdout.write_all(style::RESET)?;
+                }
+                stdout.write_all(b"\n")?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // prints the new lines once like context, with how much the indentation changed by
+    fn print_reindented<
+        T: Write,
+        S: AsRef<str>,
+        F: Fn([usize; 2], Option<&str>, Option<&str>, Option<&str>)->S
+    >(
+        &self,
+        stdout: &mut BufWriter<T>,
+        style: style::Style,
+        style_opts: &super::style::StyleOpts,
+        last: bool,
+        format_lineno: F,
+    ) -> Result<()> {
+
+        let lines = [0, 1].map(|i| side_lines(self, i));
+
+        let mut line_numbers = [self.parts[0].first_lineno(0), self.parts[0].first_lineno(1)];
+        let mut syntax = style.syntax.map(Highlighter::new);
+        for (left, right) in lines[0].iter().zip(lines[1].iter()) {
+            if style.line_numbers {
+                stdout.write_all(format_lineno(
+                    line_numbers,
+                    Some(&style_opts.lineno), Some(&style_opts.lineno),
+                    Some(&style_opts.lineno_bar),
+                ).as_ref().as_bytes())?;
+            }
+            if style.signs {
+                stdout.write_all(style::SIGN[2])?;
+            }
+            // the change is printed over the new indentation, so the line stays where it is
+            let (indent_words, indent) = indentation(right);
+            let delta = format!("{:+}", indent as isize - indentation(left).1 as isize);
+            stdout.write_all(style_opts.diff_reindent.as_bytes())?;
+            stdout.write_all(delta.as_bytes())?;
+            stdout.write_all(style_opts.diff_context.as_bytes())?;
+            stdout.write_all(" ".repeat(indent - delta.len()).as_bytes())?;
+
+            for (j, word) in right.iter().enumerate() {
+                if *word == b"\n" {
+                    stdout.write_all(style::RESET)?;
+                }
+                let class = syntax.as_mut().and_then(|h| h.next(word, right.get(j+1).map(|w| w.as_bytes())));
+                if j < indent_words {
+                    continue
+                }
+                write_word(stdout, word, class, style_opts.diff_context.as_bytes(), Show::new(style.show_invisibles, true), style_opts)?;
+            }
+            line_numbers[0] += 1;
+            line_numbers[1] += 1;
+        }
+
+        // print the no newline message
+        if last
+        && let Some(line) = lines[1].last()
+        && line.last().is_some_and(|w| *w != b"\n") {
+            stdout.write_all(style::RESET)?;
+            stdout.write_all(b"\n")?;
+            stdout.write_all(style_opts.diff_context.as_bytes())?;
+            stdout.write_all(b"\\ No newline at end of file\n")?;
+        }
+
+        Ok(())
+    }
 
     pub fn print<
         T: Write,
@@ -303,7 +731,7 @@ impl Block<'_> {
         stdout: &mut BufWriter<T>,
         merge_markers: Option<&super::hunk::MergeMarkers>,
         style: style::Style,
-        style_opts: &super::StyleOpts,
+        style_opts: &super::style::StyleOpts,
         last: bool,
         format_lineno: F,
     ) -> Result<()> {
@@ -311,18 +739,31 @@ impl Block<'_> {
         if self.parts.is_empty() {
             return Ok(())
         }
+        if let Some(width) = style.side_by_side {
+            return self.print_side_by_side(stdout, merge_markers, style, style_opts, last, width)
+        }
         let mut line_numbers = [self.parts[0].first_lineno(0), self.parts[0].first_lineno(1)];
 
-        if !style.show_both && self.parts.iter().all(|p| p.matches || (p.is_empty(0) && p.is_empty(1))) {
+        if !style.show_both && self.matches(style) {
             // this is entirely matching
 
             let mut newline = true;
+            let mut syntax = style.syntax.map(Highlighter::new);
             for part in self.parts.iter() {
-                if !part.matches {
+                let ignored = ignored(part, style);
+                if !part.matches && !ignored {
                     continue
                 }
 
-                let words = part.get(0);
+                // print the new whitespace
+                let words = part.get(if ignored { 1 } else { 0 });
+                let highlight = if ignored { &style_opts.diff_ignored_space } else { &style_opts.diff_context };
+                if words.is_empty() {
+                    continue
+                }
+                if ignored && !newline {
+                    stdout.write_all(highlight.as_bytes())?;
+                }
                 let last = words.len() - 1;
                 for (j, word) in words.iter().enumerate() {
                     if newline {
@@ -336,7 +777,7 @@ impl Block<'_> {
                         if style.signs {
                             stdout.write_all(style::SIGN[2])?;
                         }
-                        stdout.write_all(style_opts.diff_context.as_bytes())?;
+                        stdout.write_all(highlight.as_bytes())?;
                         newline = false;
                     }
 
@@ -347,7 +788,8 @@ impl Block<'_> {
                     if *word == b"\n" {
                         stdout.write_all(style::RESET)?;
                     }
-                    stdout.write_all(word)?;
+                    let class = syntax.as_mut().and_then(|h| h.next(word, words.get(j+1).map(|w| w.as_bytes())));
+                    write_word(stdout, word, class, highlight.as_bytes(), Show::new(style.show_invisibles, false), style_opts)?;
 
                     if *word == b"\n" {
                         line_numbers[0] += 1;
@@ -355,6 +797,9 @@ impl Block<'_> {
                         newline = true;
                     }
                 }
+                if ignored {
+                    stdout.write_all(style_opts.diff_context.as_bytes())?;
+                }
 
             }
 
@@ -369,22 +814,32 @@ impl Block<'_> {
             return Ok(())
         }
 
+        if style.reindent && !style.show_both && self.reindented() {
+            return self.print_reindented(stdout, style, style_opts, last, format_lineno)
+        }
+
         let score = self.score();
         let inline = style.inline && (score > Block::CUTOFF || self.parts.iter().all(|p| p.inlineable()));
         // let inline = style.inline && self.parts.iter().all(|p| p.inlineable());
 
-        let outer_loop = if inline { 0..=0 } else { 0..=1 };
+        let show = Show::new(style.show_invisibles, true);
+        let outer_loop = if inline { 0..=0 } else if let Some(side) = style.side { side..=side } else { 0..=1 };
+        let mut syntax = [0, 1].map(|_| style.syntax.map(Highlighter::new));
         for i in outer_loop {
             let mut newline = true;
             let mut insert = false;
 
             for part in self.parts.iter() {
+                let ignored = ignored(part, style);
                 if !inline && part.is_empty(i) {
-                    insert = score > 0.;
+                    insert = score > 0. && !ignored;
                     continue
                 }
 
-                let highlight = if !part.matches {
+                let ignored_space = style_opts.diff_ignored_space.as_bytes().into();
+                let highlight = if ignored {
+                    [ignored_space, ignored_space]
+                } else if !part.matches {
                     style.diff_non_matching
                 } else if inline {
                     [style.diff_matching_inline, style.diff_matching_inline]
@@ -392,18 +847,23 @@ impl Block<'_> {
                     style.diff_matching
                 };
 
-                let inner_loop = if inline && !part.matches { 0..=1 } else { i..=i };
+                let pieces = if style.char_diff { char_differ::diff_part(part) } else { None };
+                let inner_loop = if inline && !part.matches && !ignored { 0..=1 } else { i..=i };
                 for i in inner_loop {
                     stdout.write_all(highlight[i])?;
 
-                    let words = part.get(i);
+                    // print only the new whitespace if inline
+                    let words = part.get(if ignored && inline { 1 } else { i });
                     if words.is_empty() {
                         stdout.write_all(style::RESET)?;
                         continue
                     }
 
+                    let mut offset = 0;
                     let last = words.len() - 1;
                     for (j, word) in words.iter().enumerate() {
+                        let word_offset = offset;
+                        offset += word.len();
 
                         if newline {
                             if style.line_numbers {
@@ -441,9 +901,17 @@ impl Block<'_> {
 
                         let trailing_ws = words[last] == b"\n" && words[j..last].iter().all(|&w| w.is_ascii_whitespace());
 
+                        let following = words.get(j+1).map(|w| w.as_bytes());
+                        let class = syntax[i].as_mut().and_then(|h| h.next(word, following));
+                        if inline && part.matches && let Some(h) = &mut syntax[1-i] {
+                            // keep the other side in step
+                            h.next(word, following);
+                        }
+
                         if insert {
                             // add an insertion marker
-                            // write only one char
+                            // write only the first grapheme, which may be several bytes
+                            let (first, rest) = word.split_at(first_grapheme(word));
                             stdout.write_all(if i == 0 { &style_opts.diff_insert_left } else { &style_opts.diff_matching_right }.as_bytes())?;
                             if trailing_ws {
                                 stdout.write_all(style_opts.diff_trailing_ws.as_bytes())?;
@@ -451,12 +919,15 @@ impl Block<'_> {
                             if *word == b"\n" {
                                 stdout.write_all(style::RESET)?;
                             }
-                            stdout.write_all(&word[0..1])?;
+                            if let Some(class) = class {
+                                stdout.write_all(style_opts.syntax(class).as_bytes())?;
+                            }
+                            stdout.write_all(&invisibles::replace(first, show, style_opts, &[]))?;
                             if trailing_ws {
                                 stdout.write_all(style_opts.diff_trailing_ws.as_bytes())?;
                             }
                             stdout.write_all(highlight[i])?;
-                            stdout.write_all(&word[1..])?;
+                            write_word(stdout, rest, class, highlight[i], show, style_opts)?;
                             insert = false;
                         } else {
                             if trailing_ws {
@@ -465,7 +936,17 @@ impl Block<'_> {
                             if *word == b"\n" {
                                 stdout.write_all(style::RESET)?;
                             }
-                            stdout.write_all(word)?;
+                            if let Some(pieces) = &pieces {
+                                // only the characters that changed keep the non matching highlight
+                                for (piece, changed) in word_pieces(word, word_offset, Some(&pieces[i])) {
+                                    let highlight = if changed { highlight[i] } else { style.diff_matching[i] };
+                                    stdout.write_all(highlight)?;
+                                    write_word(stdout, piece, class, highlight, show, style_opts)?;
+                                }
+                                stdout.write_all(highlight[i])?;
+                            } else {
+                                write_word(stdout, word, class, highlight[i], show, style_opts)?;
+                            }
                         }
                     }
                 }
@@ -34,22 +34,7 @@ impl<'a> BlockMaker<'a> {
             for (lineno, line) in hunk.get(i).iter().enumerate() {
                 let oldlen = w.len();
                 line_to_word[i].push(oldlen);
-                super::regexes::byte_regex!(
-                    r"[A-Z][A-Z_]*[A-Z]\d*"
-                    r"|[A-Z][a-z0-9]*[a-z]"
-                    r"|[a-z0-9]+[a-z]"
-                    r"|\d+"
-                    r"|\s"
-                    r"|[-!=~+]="
-                    r"|(?:"
-                        r"(?:[\xc0-\xdf][\x80-\xbf])"
-                        r"|(?:[\xe0-\xef][\x80-\xbf][\x80-\xbf])"
-                        r"|(?:[\xf0-\xf7][\x80-\xbf][\x80-\xbf][\x80-\xbf])"
-                    r")+"
-                    "|."
-                    "|\n",
-                    |r| { w.extend(r.find_iter(line).map(|m| Bytes::from(m.as_bytes()))) }
-                );
+                w.extend(tokeniser.split(line).into_iter().map(Bytes::from));
                 tokens[i].extend(w[oldlen..].iter().map(|x| tokeniser.map(x)));
                 for _ in oldlen..w.len() {
                     word_to_line[i].push(lineno);
@@ -0,0 +1,258 @@
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsString;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::Path;
+use anyhow::{Result, Context};
+use super::printer::{Event, Printer};
+use super::tokeniser::Tokeniser;
+use super::file_differ::{self, FileDiffer};
+use super::glob::GlobFilter;
+use super::style::IgnoreSpace;
+
+// FILE: reason, like diff's messages
+fn io_error(file: &Path, error: std::io::Error) -> anyhow::Error {
+    let reason = error.to_string();
+    let reason = reason.split(" (os error").next().unwrap_or(&reason);
+    anyhow::anyhow!("{}: {}", file.display(), reason)
+}
+
+fn read_file(file: &Path, filter: Option<&str>) -> Result<Vec<u8>> {
+    let stdin = file == Path::new("-");
+
+    let Some(filter) = filter else {
+        return if stdin {
+            let mut data = vec![];
+            std::io::stdin().lock().read_to_end(&mut data).map_err(|e| io_error(file, e))?;
+            Ok(data)
+        } else {
+            std::fs::read(file).map_err(|e| io_error(file, e))
+        }
+    };
+
+    let stdin = if stdin {
+        Stdio::inherit()
+    } else {
+        std::fs::File::open(file).map_err(|e| io_error(file, e))?.into()
+    };
+    let output = Command::new("bash")
+        .arg("-c")
+        .arg(filter)
+        .stdin(stdin)
+        .stderr(Stdio::inherit())
+        .output()?;
+    // otherwise a broken filter would look like an empty file
+    if !output.status.success() {
+        anyhow::bail!("{}: filter {:?} failed with {}", file.display(), filter, output.status);
+    }
+    Ok(output.stdout)
+}
+
+// what diff calls each kind of file
+fn file_kind(path: &Path) -> std::io::Result<&'static str> {
+    // follow symlinks like diff does, so a dangling one is missing
+    let kind = path.metadata()?.file_type();
+    Ok(if kind.is_dir() {
+        "directory"
+    } else if kind.is_file() {
+        "regular file"
+    } else if kind.is_fifo() {
+        "fifo"
+    } else if kind.is_char_device() {
+        "character special file"
+    } else if kind.is_block_device() {
+        "block special file"
+    } else if kind.is_socket() {
+        "socket"
+    } else {
+        "special file"
+    })
+}
+
+fn read_dir(dir: &Path) -> Result<BTreeMap<OsString, Result<&'static str>>> {
+    let mut entries = BTreeMap::new();
+    for entry in std::fs::read_dir(dir).map_err(|e| io_error(dir, e))? {
+        let entry = entry.map_err(|e| io_error(dir, e))?;
+        let path = entry.path();
+        entries.insert(entry.file_name(), file_kind(&path).map_err(|e| io_error(&path, e)));
+    }
+    Ok(entries)
+}
+
+fn dir_id(dir: &Path) -> Result<(u64, u64)> {
+    let metadata = dir.metadata().map_err(|e| io_error(dir, e))?;
+    Ok((metadata.dev(), metadata.ino()))
+}
+
+pub struct Builtin<'a> {
+    pub printer: &'a mut dyn Printer,
+    pub tokeniser: Tokeniser,
+    pub filter: Option<&'a str>,
+    pub context: usize,
+    pub recursive: bool,
+    pub globs: GlobFilter,
+    pub color: bool,
+    pub ignore_space: Option<IgnoreSpace>,
+    // set when a file in a directory couldn't be compared, which like diff is reported and skipped
+    pub trouble: bool,
+}
+
+impl Builtin<'_> {
+
+    fn label(&self, file: &Path) -> String {
+        match self.filter {
+            Some(filter) => format!("{} | {}", file.display(), filter),
+            None => file.display().to_string(),
+        }
+    }
+
+    pub fn diff(&mut self, files: [&str; 2], labels: &[String]) -> Result<bool> {
+        let files = files.map(Path::new);
+        let is_dir = files.map(|f| f != Path::new("-") && f.is_dir());
+
+        if is_dir[0] && is_dir[1] {
+            return self.diff_dirs(files, Path::new(""), &mut vec![])
+        }
+
+        // compare a file against the file with the same name in the directory
+        let mut paths = files.map(|f| f.to_path_buf());
+        for i in 0..=1 {
+            if is_dir[i] {
+                let name = files[1-i].file_name().with_context(|| format!("cannot compare {} to a directory", files[1-i].display()))?;
+                paths[i].push(name);
+            }
+        }
+
+        let labels = [0, 1].map(|i| labels.get(i).cloned().unwrap_or_else(|| self.label(&paths[i])));
+        self.diff_files([&paths[0], &paths[1]], labels, None)
+    }
+
+    // ancestors are the directories being compared further up, to stop at symlink loops
+    fn diff_dirs(&mut self, dirs: [&Path; 2], relative: &Path, ancestors: &mut Vec<[(u64, u64); 2]>) -> Result<bool> {
+        let ids = [dir_id(dirs[0])?, dir_id(dirs[1])?];
+        if let Some(i) = (0..=1).find(|&i| ancestors.iter().any(|a| a[i] == ids[i])) {
+            self.print_message(format!("{}: recursive directory loop", dirs[i].display()))?;
+            return Ok(true)
+        }
+        ancestors.push(ids);
+        let differs = self.diff_dir_entries(dirs, relative, ancestors);
+        ancestors.pop();
+        differs
+    }
+
+    fn diff_dir_entries(&mut self, dirs: [&Path; 2], relative: &Path, ancestors: &mut Vec<[(u64, u64); 2]>) -> Result<bool> {
+        let entries = [read_dir(dirs[0])?, read_dir(dirs[1])?];
+        let names: BTreeSet<_> = entries[0].keys().chain(entries[1].keys()).collect();
+
+        let mut differs = false;
+        for name in names {
+            let relative = relative.join(name);
+            if self.globs.excluded(relative.as_os_str().as_encoded_bytes()) {
+                continue
+            }
+            let kinds = match [entries[0].get(name), entries[1].get(name)] {
+                [Some(Err(error)), Some(_)] | [Some(_), Some(Err(error))] => {
+                    eprintln!("dyff: {error}");
+                    self.trouble = true;
+                    continue
+                },
+                // a file only on one side is only named, so it doesn't matter what kind it is
+                kinds => kinds.map(|k| k.map(|k| *k.as_ref().unwrap_or(&"unknown"))),
+            };
+            // --include only applies to files
+            if !kinds.contains(&Some("directory")) && !self.globs.included(relative.as_os_str().as_encoded_bytes()) {
+                continue
+            }
+
+            let paths = dirs.map(|d| d.join(name));
+            let paths = [paths[0].as_path(), paths[1].as_path()];
+            match kinds {
+                [Some("directory"), Some("directory")] if self.recursive => {
+                    differs = self.diff_dirs(paths, &relative, ancestors)? || differs;
+                },
+                [Some("directory"), Some("directory")] => {
+                    self.print_message(format!("Common subdirectories: {} and {}", paths[0].display(), paths[1].display()))?;
+                },
+                [Some("regular file"), Some("regular file")] => {
+                    let labels = paths.map(|p| self.label(p));
+                    let header = format!("diff{}", if self.recursive { " -r" } else { "" });
+                    differs = self.diff_files(paths, labels, Some(&header))? || differs;
+                },
+                // fifos and devices would block or never end, so they are not read
+                [Some(left), Some(right)] => {
+                    self.print_message(format!(
+                        "File {} is a {} while file {} is a {}",
+                        paths[0].display(), left,
+                        paths[1].display(), right,
+                    ))?;
+                    differs = true;
+                },
+                [_, _] => {
+                    let side = if kinds[0].is_some() { 0 } else { 1 };
+                    let message = format!("Only in {}: {}", dirs[side].display(), name.display());
+                    self.printer.print(&mut self.tokeniser, Event::OnlyIn{side, message: message.as_bytes()})?;
+                    differs = true;
+                },
+            }
+        }
+        Ok(differs)
+    }
+
+    fn print_message(&mut self, message: String) -> Result<()> {
+        self.printer.print(&mut self.tokeniser, Event::Message{message: message.as_bytes()})
+    }
+
+    fn diff_files(&mut self, files: [&Path; 2], labels: [String; 2], header: Option<&str>) -> Result<bool> {
+        let data = [read_file(files[0], self.filter)?, read_file(files[1], self.filter)?];
+        if data[0] == data[1] {
+            return Ok(false)
+        }
+
+        if data.iter().any(|d| file_differ::is_binary(d)) {
+            self.print_message(format!("Binary files {} and {} differ", labels[0], labels[1]))?;
+            return Ok(true)
+        }
+
+        self.tokeniser.set_filename(files[1].as_os_str().as_encoded_bytes());
+        let lines = data.map(|d| file_differ::split_lines(&d));
+        let lines = [lines[0].as_slice(), lines[1].as_slice()];
+        let hunks = FileDiffer::new(lines[0], lines[1], &mut self.tokeniser, self.ignore_space).get_hunks(self.context);
+        // like diff, files that only differ in whitespace being ignored print nothing
+        if hunks.is_empty() {
+            return Ok(false)
+        }
+
+        if !self.color {
+            let mut stdout = vec![];
+            if let Some(header) = header {
+                writeln!(stdout, "{} {} {}", header, files[0].display(), files[1].display())?;
+            }
+            writeln!(stdout, "--- {}", labels[0])?;
+            writeln!(stdout, "+++ {}", labels[1])?;
+            for h in hunks {
+                h.write_unified(&mut stdout, lines)?;
+            }
+            self.printer.print(&mut self.tokeniser, Event::Raw{line: &stdout})?;
+            return Ok(true)
+        }
+
+        if let Some(header) = header {
+            let files = files.map(|f| f.as_os_str().as_encoded_bytes());
+            self.printer.print(&mut self.tokeniser, Event::DiffHeader{header: header.as_bytes(), filename1: files[0], filename2: files[1], trailer: b""})?;
+        }
+        self.printer.print(&mut self.tokeniser, Event::Filename{
+            left: Some(labels[0].as_str().into()),
+            right: Some(labels[1].as_str().into()),
+            rename: false,
+            prefixes: [b"", b""],
+        })?;
+        for h in hunks {
+            let header = h.header();
+            self.printer.print(&mut self.tokeniser, Event::HunkHeader{header: header.as_bytes(), context: b""})?;
+            let mut hunk = h.to_hunk(lines);
+            self.printer.print(&mut self.tokeniser, Event::Hunk{hunk: &mut hunk, line_numbers: h.line_numbers(), merge_markers: None})?;
+        }
+        Ok(true)
+    }
+}
@@ -0,0 +1,86 @@
+use std::ops::Range;
+use bstr::ByteSlice;
+use super::part::Part;
+use super::whitespace::CheckAllWhitespace;
+
+// only words up to this many characters are diffed by character
+const MAX_CHARS: usize = 32;
+// how much of the words must be in common, so that unrelated words are left alone
+const MIN_SIMILARITY: f64 = 0.5;
+
+// byte ranges of some text and whether they changed
+pub type Pieces = Vec<(Range<usize>, bool)>;
+
+// diffs the two sides of a short non matching part by character,
+// e.g. for typos (recieve, receive) or version bumps (1.0.86, 1.0.89)
+pub fn diff_part(part: &Part) -> Option<[Pieces; 2]> {
+    if part.matches || part.is_empty(0) || part.is_empty(1) {
+        return None
+    }
+    // only within words, not across lines or spaces
+    if [0, 1].iter().any(|&i| part.get(i).iter().any(|w| w.is_ascii_whitespace())) {
+        return None
+    }
+    let texts = [0, 1].map(|i| bstr::concat(part.get(i)));
+    diff_chars(&texts[0], &texts[1])
+}
+
+pub fn diff_chars(left: &[u8], right: &[u8]) -> Option<[Pieces; 2]> {
+    let texts = [left, right];
+    let chars = texts.map(|t| t.grapheme_indices().map(|(start, end, _)| start..end).collect::<Vec<_>>());
+    let [n, m] = [chars[0].len(), chars[1].len()];
+    if n == 0 || m == 0 || n > MAX_CHARS || m > MAX_CHARS {
+        return None
+    }
+    let same = |i: usize, j: usize| texts[0][chars[0][i].clone()] == texts[1][chars[1][j].clone()];
+
+    // longest common subsequence
+    let mut table = vec![vec![0; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            table[i][j] = if same(i, j) { table[i+1][j+1] + 1 } else { table[i+1][j].max(table[i][j+1]) };
+        }
+    }
+    if (2 * table[0][0]) as f64 / ((n + m) as f64) < MIN_SIMILARITY {
+        return None
+    }
+
+    let mut changed = [vec![true; n], vec![true; m]];
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if same(i, j) {
+            changed[0][i] = false;
+            changed[1][j] = false;
+            i += 1;
+            j += 1;
+        } else if table[i+1][j] >= table[i][j+1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    // join up runs of characters that all changed or all did not
+    Some([0, 1].map(|side| {
+        let mut pieces: Pieces = vec![];
+        for (range, &changed) in chars[side].iter().zip(&changed[side]) {
+            match pieces.last_mut() {
+                Some((last, c)) if *c == changed => last.end = range.end,
+                _ => pieces.push((range.clone(), changed)),
+            }
+        }
+        pieces
+    }))
+}
+
+// the pieces of a word that starts at offset, and whether they changed
+pub fn word_pieces<'a>(word: &'a [u8], offset: usize, pieces: Option<&Pieces>) -> Vec<(&'a [u8], bool)> {
+    let Some(pieces) = pieces else {
+        return vec![(word, true)]
+    };
+    let end = offset + word.len();
+    pieces.iter()
+        .filter(|(range, _)| range.start < end && offset < range.end)
+        .map(|(range, changed)| (&word[range.start.max(offset) - offset .. range.end.min(end) - offset], *changed))
+        .collect()
+}
@@ -0,0 +1,145 @@
+#[derive(Clone, Copy, PartialEq, Debug, clap::ValueEnum)]
+pub enum ColorDepth {
+    Truecolor,
+    #[value(name = "256")]
+    Ansi256,
+    #[value(name = "16")]
+    Ansi16,
+    Auto,
+}
+
+// the usual xterm colours for the 16 colour palette
+const PALETTE_16: [[u8; 3]; 16] = [
+    [0, 0, 0], [205, 0, 0], [0, 205, 0], [205, 205, 0],
+    [0, 0, 238], [205, 0, 205], [0, 205, 205], [229, 229, 229],
+    [127, 127, 127], [255, 0, 0], [0, 255, 0], [255, 255, 0],
+    [92, 92, 255], [255, 0, 255], [0, 255, 255], [255, 255, 255],
+];
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    a.iter().zip(b).map(|(&a, b)| (a as i32 - b as i32).pow(2) as u32).sum()
+}
+
+fn ansi256_to_rgb(n: u8) -> [u8; 3] {
+    match n {
+        0..=15 => PALETTE_16[n as usize],
+        16..=231 => {
+            let n = n - 16;
+            [n / 36, n / 6 % 6, n % 6].map(|i| CUBE_LEVELS[i as usize])
+        },
+        _ => [8 + 10 * (n - 232); 3],
+    }
+}
+
+fn rgb_to_ansi256(rgb: [u8; 3]) -> u8 {
+    let nearest_level = |c: u8| (0..6).min_by_key(|&i| (CUBE_LEVELS[i] as i32 - c as i32).abs()).unwrap();
+    let cube = rgb.map(nearest_level);
+    let cube_index = 16 + 36 * cube[0] + 6 * cube[1] + cube[2];
+
+    let gray = (rgb.iter().map(|&c| c as usize).sum::<usize>() / 3).clamp(8, 238);
+    let gray_index = 232 + (gray - 8) / 10;
+
+    [cube_index as u8, gray_index as u8].into_iter()
+        .min_by_key(|&n| distance(rgb, ansi256_to_rgb(n)))
+        .unwrap()
+}
+
+fn rgb_to_ansi16(rgb: [u8; 3]) -> u8 {
+    (0..16).min_by_key(|&i| distance(rgb, PALETTE_16[i as usize])).unwrap()
+}
+
+impl ColorDepth {
+    pub fn resolve(self) -> Self {
+        if self != Self::Auto {
+            return self
+        }
+
+        let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            return Self::Truecolor
+        }
+        match std::env::var("TERM") {
+            Ok(term) if term.contains("truecolor") || term.contains("direct") => Self::Truecolor,
+            Ok(term) if term.contains("256") => Self::Ansi256,
+            Ok(term) if !term.is_empty() => Self::Ansi16,
+            // nothing to go on
+            _ => Self::Truecolor,
+        }
+    }
+
+    // rewrites the colours in the params of one sgr sequence
+    // colours are either separate params (38;5;n) or the sub-params of one (38:5:n)
+    fn downgrade_params(self, params: &[&str], sep: &str) -> String {
+        let mut output: Vec<String> = vec![];
+
+        let mut i = 0;
+        while i < params.len() {
+            let kind = params[i];
+            // sub-params may have an empty colour space id before the rgb, e.g. 38:2::r:g:b
+            let skip = (sep == ":" && params.get(i+2) == Some(&"") && i + 5 < params.len()) as usize;
+            let color = match (kind, params.get(i+1).copied()) {
+                ("38" | "48" | "58", Some("2")) if i + 4 + skip < params.len() => {
+                    let rgb = [2, 3, 4].map(|j| params[i+j+skip].parse::<u8>().ok());
+                    let rgb = if let [Some(r), Some(g), Some(b)] = rgb { Some([r, g, b]) } else { None };
+                    rgb.map(|rgb| (rgb, 5 + skip))
+                },
+                ("38" | "48" | "58", Some("5")) if i + 2 < params.len() => {
+                    params[i+2].parse::<u8>().ok().map(|n| (ansi256_to_rgb(n), 3))
+                },
+                _ => None,
+            };
+
+            let Some((rgb, len)) = color else {
+                if kind.contains(':') {
+                    let sub_params: Vec<_> = kind.split(':').collect();
+                    let sub_params = self.downgrade_params(&sub_params, ":");
+                    // an empty param would reset the style
+                    if !sub_params.is_empty() {
+                        output.push(sub_params);
+                    }
+                } else {
+                    output.push(kind.to_owned());
+                }
+                i += 1;
+                continue
+            };
+            i += len;
+
+            match self {
+                Self::Ansi256 => {
+                    let n = if len == 3 { params[i-1].parse().unwrap() } else { rgb_to_ansi256(rgb) };
+                    output.push(format!("{kind}{sep}5{sep}{n}"));
+                },
+                Self::Ansi16 if kind == "58" => {
+                    // there is no 16 colour underline colour
+                },
+                Self::Ansi16 => {
+                    let n = rgb_to_ansi16(rgb);
+                    let base = match (kind, n < 8) {
+                        ("38", true) => 30,
+                        ("38", false) => 90 - 8,
+                        (_, true) => 40,
+                        (_, false) => 100 - 8,
+                    };
+                    output.push((base + n).to_string());
+                },
+                _ => output.push(params[i-len .. i].join(sep)),
+            }
+        }
+        output.join(sep)
+    }
+
+    // rewrites the colours in all the sgr sequences in style
+    pub fn downgrade(self, style: &str) -> String {
+        match self.resolve() {
+            Self::Truecolor | Self::Auto => style.to_owned(),
+            depth => crate::regexes::regex!(r"\x1b\[([\d;:]*)m", |r| {
+                r.replace_all(style, |c: &regex::Captures| {
+                    let params: Vec<_> = c[1].split(';').collect();
+                    format!("\x1b[{}m", depth.downgrade_params(&params, ";"))
+                }).into_owned()
+            }),
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use anyhow::{Result, Context};
+
+pub fn default_path() -> Option<PathBuf> {
+    let dir = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| Path::new(&h).join(".config")))?;
+    Some(dir.join("dyff").join("config.toml"))
+}
+
+// the hidden flag that turns a flag off again, e.g. --no-signs, or --line-numbers for --no-line-numbers
+pub fn negate(option: &str) -> String {
+    match option.strip_prefix("no-") {
+        Some(option) => option.to_owned(),
+        None => format!("no-{option}"),
+    }
+}
+
+fn value_to_args(key: &str, value: &toml::Value, args: &mut Vec<String>) -> Result<()> {
+    match value {
+        toml::Value::Boolean(true) => args.push(format!("--{key}")),
+        // turns off the flag if a profile or theme has turned it on
+        toml::Value::Boolean(false) => args.push(format!("--{}", negate(key))),
+        toml::Value::String(value) => args.push(format!("--{key}={value}")),
+        toml::Value::Integer(value) => args.push(format!("--{key}={value}")),
+        toml::Value::Float(value) => args.push(format!("--{key}={value}")),
+        toml::Value::Array(values) => {
+            for value in values {
+                value_to_args(key, value, args)?;
+            }
+        },
+        _ => anyhow::bail!("unsupported value for {key}: {}", value.type_str()),
+    }
+    Ok(())
+}
+
+fn table_to_args(table: &toml::Table, options: &[&str], args: &mut Vec<String>) -> Result<()> {
+    for (key, value) in table {
+        let key = key.replace('_', "-");
+        if !options.contains(&key.as_str()) {
+            anyhow::bail!("unknown option: {key}")
+        }
+        value_to_args(&key, value, args)?;
+    }
+    Ok(())
+}
+
+// converts the config file into command line arguments
+// top level keys apply always, keys in [profile.NAME] only when that profile is selected
+// the default config file may be missing, unless a profile from it was asked for
+pub fn load(path: &Path, default: bool, profile: Option<&str>, options: &[&str]) -> Result<Vec<String>> {
+    let data = match std::fs::read_to_string(path) {
+        Err(e) if default && e.kind() == std::io::ErrorKind::NotFound => match profile {
+            Some(profile) => anyhow::bail!("no such profile {profile}: {} does not exist", path.display()),
+            None => return Ok(vec![]),
+        },
+        data => data.with_context(|| format!("failed to read {}", path.display()))?,
+    };
+    let mut config: toml::Table = data.parse().with_context(|| format!("failed to parse {}", path.display()))?;
+
+    let profiles = match config.remove("profile") {
+        Some(toml::Value::Table(profiles)) => profiles,
+        Some(_) => anyhow::bail!("{}: profile must be a table", path.display()),
+        None => toml::Table::new(),
+    };
+    // the profile may also be set in the config file itself
+    let default_profile = match config.remove("default-profile") {
+        Some(toml::Value::String(profile)) => Some(profile),
+        Some(_) => anyhow::bail!("{}: default-profile must be a string", path.display()),
+        None => None,
+    };
+
+    let mut args = vec![];
+    table_to_args(&config, options, &mut args).with_context(|| format!("invalid config in {}", path.display()))?;
+
+    if let Some(profile) = profile.or(default_profile.as_deref()) {
+        match profiles.get(profile) {
+            Some(toml::Value::Table(table)) => {
+                table_to_args(table, options, &mut args).with_context(|| format!("invalid profile {profile} in {}", path.display()))?;
+            },
+            _ => anyhow::bail!("no such profile {profile} in {}", path.display()),
+        }
+    }
+
+    Ok(args)
+}
+
+fn parse_git_bool(value: Option<&str>) -> Option<bool> {
+    match value.map(|v| v.to_ascii_lowercase()).as_deref() {
+        // a key with no value is true
+        None | Some("true" | "yes" | "on" | "1") => Some(true),
+        Some("false" | "no" | "off" | "0" | "") => Some(false),
+        _ => None,
+    }
+}
+
+// converts the dyff.* keys in git config into command line arguments
+// options are the long option names and whether they take a value
+pub fn git_config(options: &[(&str, bool)]) -> Result<Vec<String>> {
+    let output = match Command::new("git").args(["config", "--null", "--get-regexp", r"^dyff\."]).stderr(Stdio::null()).output() {
+        Ok(output) => output,
+        // no git, no config
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut args = vec![];
+    // entries are in order of precedence, so later ones override earlier ones
+    for entry in output.stdout.split(|&c| c == 0).filter(|e| !e.is_empty()) {
+        let entry = std::str::from_utf8(entry).context("git config is not valid utf8")?;
+        let (key, value) = match entry.split_once('\n') {
+            Some((key, value)) => (key, Some(value)),
+            None => (entry, None),
+        };
+        // git lowercases keys, so dyff.diffMatchingLeft becomes dyff.diffmatchingleft
+        let name = key.strip_prefix("dyff.").unwrap_or(key).replace('-', "");
+        // git config is shared with other versions of dyff, so don't fail on options this one doesn't have
+        let Some(&(option, takes_value)) = options.iter().find(|(o, _)| o.replace('-', "") == name) else {
+            eprintln!("warning: ignoring unknown option in git config: {key}");
+            continue
+        };
+
+        if takes_value {
+            let value = value.with_context(|| format!("missing value for {key} in git config"))?;
+            args.push(format!("--{option}={value}"));
+        } else {
+            match parse_git_bool(value) {
+                Some(true) => args.push(format!("--{option}")),
+                Some(false) => args.push(format!("--{}", negate(option))),
+                None => anyhow::bail!("invalid boolean for {key} in git config: {}", value.unwrap_or("")),
+            }
+        }
+    }
+    Ok(args)
+}
@@ -0,0 +1,417 @@
+use std::ops::Range;
+use std::collections::HashMap;
+use std::io::Write;
+use bstr::ByteSlice;
+use super::hunk::Hunk;
+use super::tokeniser::{Token, Tokeniser};
+use super::style::IgnoreSpace;
+use super::types::*;
+
+// (matches, left lines, right lines)
+pub type Opcode = (bool, Range<usize>, Range<usize>);
+
+pub fn split_lines(data: &[u8]) -> Line {
+    data.split_inclusive(|&c| c == b'\n').map(|l| l.into()).collect()
+}
+
+pub fn is_binary(data: &[u8]) -> bool {
+    // same heuristic as git: look for a NUL in the first few KB
+    data[..data.len().min(8000)].contains(&0)
+}
+
+// a line with the whitespace that is being ignored taken out, so that lines only differing in it match
+fn without_space(line: &[u8], ignore: IgnoreSpace) -> Vec<u8> {
+    let (text, newline) = match line.strip_suffix(b"\n") {
+        Some(text) => (text, b"\n".as_slice()),
+        None => (line, b"".as_slice()),
+    };
+    let mut out = Vec::with_capacity(line.len());
+    let mut space = false;
+    for &c in text {
+        if c.is_ascii_whitespace() {
+            space = true;
+            continue
+        }
+        // whitespace at the end of the line is never added back
+        if space && ignore == IgnoreSpace::Change {
+            out.push(b' ');
+        }
+        space = false;
+        out.push(c);
+    }
+    out.extend_from_slice(newline);
+    out
+}
+
+fn format_range(range: &Range<usize>) -> String {
+    match range.len() {
+        0 => format!("{},0", range.start),
+        1 => format!("{}", range.start + 1),
+        len => format!("{},{}", range.start + 1, len),
+    }
+}
+
+#[derive(Debug)]
+pub struct DiffHunk {
+    pub ops: Vec<Opcode>,
+}
+
+impl DiffHunk {
+    pub fn range(&self, i: usize) -> Range<usize> {
+        let get = |op: &Opcode| if i == 0 { op.1.clone() } else { op.2.clone() };
+        get(&self.ops[0]).start .. get(self.ops.last().unwrap()).end
+    }
+
+    pub fn line_numbers(&self) -> [usize; 2] {
+        [0, 1].map(|i| {
+            let range = self.range(i);
+            if range.is_empty() { range.start } else { range.start + 1 }
+        })
+    }
+
+    pub fn header(&self) -> String {
+        format!("@@ -{} +{} @@", format_range(&self.range(0)), format_range(&self.range(1)))
+    }
+
+    pub fn to_hunk(&self, lines: [&[Word]; 2]) -> Hunk {
+        let mut hunk = Hunk::new();
+        for i in 0..=1 {
+            hunk.get_mut(i).extend_from_slice(&lines[i][self.range(i)]);
+        }
+        hunk
+    }
+
+    pub fn write_unified<T: Write>(&self, stdout: &mut T, lines: [&[Word]; 2]) -> std::io::Result<()> {
+        let write_line = |stdout: &mut T, sign: &[u8], line: &Word| -> std::io::Result<()> {
+            stdout.write_all(sign)?;
+            stdout.write_all(line)?;
+            if !line.ends_with(b"\n") {
+                stdout.write_all(b"\n\\ No newline at end of file\n")?;
+            }
+            Ok(())
+        };
+
+        stdout.write_all(self.header().as_bytes())?;
+        stdout.write_all(b"\n")?;
+        for (matches, left, right) in self.ops.iter() {
+            if *matches {
+                for l in left.clone() {
+                    write_line(stdout, b" ", &lines[0][l])?;
+                }
+            } else {
+                for l in left.clone() {
+                    write_line(stdout, b"-", &lines[0][l])?;
+                }
+                for r in right.clone() {
+                    write_line(stdout, b"+", &lines[1][r])?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// regions up to this size (left lines times right lines) are searched for their longest match,
+// larger ones are split on the lines that occur the fewest times, like git's histogram diff
+const MAX_SEARCH: usize = 1 << 20;
+// and lines that occur more often than this are not used to split them at all
+const MAX_CHAIN: usize = 64;
+// regions with only common lines fall back to a myers diff, up to this many lines added and removed
+const MAX_EDITS: usize = 2048;
+
+pub struct FileDiffer<'a> {
+    lines: [&'a [Word]; 2],
+    tokens: [Vec<Token>; 2],
+}
+
+impl<'a> FileDiffer<'a> {
+    pub fn new(left: &'a [Word], right: &'a [Word], tokeniser: &mut Tokeniser, ignore_space: Option<IgnoreSpace>) -> Self {
+        let lines = [left, right];
+        let tokens = lines.map(|lines| lines.iter().map(|line| match ignore_space {
+            Some(ignore) => tokeniser.map(without_space(line, ignore).as_bstr()),
+            None => tokeniser.map(line.as_ref()),
+        }).collect::<Vec<_>>());
+        Self{
+            lines,
+            tokens,
+        }
+    }
+
+    fn find_longest_match(
+        &self,
+        alo: usize,
+        ahi: usize,
+        blo: usize,
+        bhi: usize,
+    ) -> Option<(usize, usize, usize)> {
+
+        let mut b2j = HashMap::<Token, Vec<usize>>::new();
+        for (j, tok) in self.tokens[1][blo..bhi].iter().enumerate() {
+            b2j.entry(*tok).or_default().push(blo + j);
+        }
+
+        let mut besti = alo;
+        let mut bestj = blo;
+        let mut bestsize = 0;
+
+        let mut j2len = HashMap::<usize, usize>::new();
+        let mut newj2len = HashMap::<usize, usize>::new();
+
+        for i in alo..ahi {
+            newj2len.clear();
+            let value = self.tokens[0][i];
+
+            for &j in b2j.get(&value).into_iter().flatten() {
+                // a[i] matches b[j]
+                let k = if j == 0 { 1 } else { j2len.get(&(j-1)).unwrap_or(&0) + 1 };
+
+                newj2len.insert(j, k);
+
+                if k > bestsize {
+                    besti = i + 1 - k;
+                    bestj = j + 1 - k;
+                    bestsize = k;
+                }
+            }
+
+            std::mem::swap(&mut j2len, &mut newj2len);
+        }
+
+        if bestsize == 0 {
+            return None
+        }
+
+        Some((besti, bestj, bestsize))
+    }
+
+    // finds the run of matching lines around the line that occurs the fewest times
+    fn find_rarest_match(
+        &self,
+        alo: usize,
+        ahi: usize,
+        blo: usize,
+        bhi: usize,
+    ) -> Option<(usize, usize, usize)> {
+
+        let [left, right] = &self.tokens;
+        let mut positions = HashMap::<Token, Vec<usize>>::new();
+        for (i, tok) in left[alo..ahi].iter().enumerate() {
+            positions.entry(*tok).or_default().push(alo + i);
+        }
+
+        // (occurrences, i, j, size)
+        let mut best: Option<(usize, usize, usize, usize)> = None;
+        let mut j = blo;
+        while j < bhi {
+            let mut next = j + 1;
+            let lowest = best.map_or(MAX_CHAIN, |b| b.0);
+            if let Some(chain) = positions.get(&right[j]) && chain.len() <= lowest {
+                for &i in chain {
+                    // extend the match both ways
+                    let before = (1 ..= (i - alo).min(j - blo)).take_while(|&k| left[i-k] == right[j-k]).count();
+                    let after = (1 .. (ahi - i).min(bhi - j)).take_while(|&k| left[i+k] == right[j+k]).count();
+                    let (i, j, size) = (i - before, j - before, before + after + 1);
+                    next = next.max(j + size);
+
+                    let occurrences = left[i .. i+size].iter().map(|tok| positions[tok].len()).min().unwrap();
+                    if best.is_none_or(|b| occurrences < b.0 || (occurrences == b.0 && size > b.3)) {
+                        best = Some((occurrences, i, j, size));
+                    }
+                }
+            }
+            // no need to look at lines already in a match
+            j = next;
+        }
+
+        best.map(|(_, i, j, size)| (i, j, size))
+    }
+
+    // the matching runs on the shortest path of a myers diff
+    fn find_myers_matches(
+        &self,
+        alo: usize,
+        ahi: usize,
+        blo: usize,
+        bhi: usize,
+    ) -> Option<Vec<(usize, usize, usize)>> {
+
+        let [left, right] = [&self.tokens[0][alo..ahi], &self.tokens[1][blo..bhi]];
+        let (n, m) = (left.len() as isize, right.len() as isize);
+        let max = MAX_EDITS.min(left.len() + right.len()) as isize;
+
+        // furthest x along each diagonal k = x - y, for each number of edits
+        let mut v = vec![0; 2 * max as usize + 3];
+        let index = |k: isize| (k + max + 1) as usize;
+        let mut trace = vec![];
+
+        for d in 0..=max {
+            for k in (-d..=d).step_by(2) {
+                let mut x = if k == -d || (k != d && v[index(k-1)] < v[index(k+1)]) {
+                    v[index(k+1)]
+                } else {
+                    v[index(k-1)] + 1
+                };
+                let mut y = x - k;
+                while x < n && y < m && left[x as usize] == right[y as usize] {
+                    x += 1;
+                    y += 1;
+                }
+                v[index(k)] = x;
+
+                if x >= n && y >= m {
+                    trace.push(v.clone());
+                    return Some(Self::myers_backtrack(&trace, index, n, m, [alo, blo]))
+                }
+            }
+            trace.push(v.clone());
+        }
+        None
+    }
+
+    fn myers_backtrack(
+        trace: &[Vec<isize>],
+        index: impl Fn(isize) -> usize,
+        mut x: isize,
+        mut y: isize,
+        offset: [usize; 2],
+    ) -> Vec<(usize, usize, usize)> {
+
+        let mut matches = vec![];
+        let mut push = |x: isize, y: isize, len: isize| {
+            if len > 0 {
+                matches.push((offset[0] + x as usize, offset[1] + y as usize, len as usize));
+            }
+        };
+
+        for d in (1..trace.len() as isize).rev() {
+            let v = &trace[d as usize - 1];
+            let k = x - y;
+            let prev_k = if k == -d || (k != d && v[index(k-1)] < v[index(k+1)]) { k + 1 } else { k - 1 };
+            let prev_x = v[index(prev_k)];
+            let prev_y = prev_x - prev_k;
+            // the snake after the edit
+            let start_x = if prev_k == k + 1 { prev_x } else { prev_x + 1 };
+            push(start_x, start_x - k, x - start_x);
+            x = prev_x;
+            y = prev_y;
+        }
+        push(0, 0, x);
+        matches
+    }
+
+    pub fn get_matching_blocks(&self) -> Vec<(usize, usize, usize)> {
+        let [left, right] = &self.tokens;
+
+        // trim the common prefix and suffix first, they are usually most of the file
+        let prefix = left.iter().zip(right).take_while(|(a, b)| a == b).count();
+        let suffix = left[prefix..].iter().rev().zip(right[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+
+        let mut matching_blocks = vec![];
+        if prefix > 0 {
+            matching_blocks.push((0, 0, prefix));
+        }
+
+        let mut queue = vec![(prefix, left.len() - suffix, prefix, right.len() - suffix)];
+        while let Some((alo, ahi, blo, bhi)) = queue.pop() {
+            let found = if (ahi - alo).saturating_mul(bhi - blo) <= MAX_SEARCH {
+                self.find_longest_match(alo, ahi, blo, bhi)
+            } else if let Some(found) = self.find_rarest_match(alo, ahi, blo, bhi) {
+                Some(found)
+            } else {
+                // every line is too common to split on
+                matching_blocks.extend(self.find_myers_matches(alo, ahi, blo, bhi).into_iter().flatten());
+                None
+            };
+
+            if let Some((i, j, k)) = found {
+                // a[alo:i] vs b[blo:j] unknown
+                // a[i:i+k] same as b[j:j+k]
+                // a[i+k:ahi] vs b[j+k:bhi] unknown
+
+                matching_blocks.push((i, j, k));
+                if alo < i && blo < j {
+                    queue.push((alo, i, blo, j));
+                }
+                if i+k < ahi && j+k < bhi {
+                    queue.push((i+k, ahi, j+k, bhi));
+                }
+            }
+        }
+
+        if suffix > 0 {
+            matching_blocks.push((left.len() - suffix, right.len() - suffix, suffix));
+        }
+        matching_blocks.sort();
+        matching_blocks
+    }
+
+    pub fn get_opcodes(&self) -> Vec<Opcode> {
+        let mut opcodes: Vec<Opcode> = vec![];
+        let mut previ = 0;
+        let mut prevj = 0;
+
+        let end = (self.lines[0].len(), self.lines[1].len(), 0);
+        for (i, j, k) in self.get_matching_blocks().into_iter().chain(std::iter::once(end)) {
+            if previ < i || prevj < j {
+                opcodes.push((false, previ..i, prevj..j));
+            }
+            if k > 0 {
+                // merge adjacent matches
+                if let Some((true, left, right)) = opcodes.last_mut() && left.end == i && right.end == j {
+                    left.end = i + k;
+                    right.end = j + k;
+                } else {
+                    opcodes.push((true, i..i+k, j..j+k));
+                }
+            }
+            previ = i + k;
+            prevj = j + k;
+        }
+
+        opcodes
+    }
+
+    pub fn get_hunks(&self, context: usize) -> Vec<DiffHunk> {
+        let mut hunks = vec![];
+        let mut ops = vec![];
+
+        let opcodes = self.get_opcodes();
+        let len = opcodes.len();
+        for (n, (matches, left, right)) in opcodes.into_iter().enumerate() {
+            if !matches {
+                ops.push((matches, left, right));
+                continue
+            }
+
+            let size = left.len();
+            let first = n == 0;
+            let last = n == len - 1;
+
+            if !first && (last || size > context * 2) {
+                // trailing context for the current hunk
+                let k = size.min(context);
+                if k > 0 {
+                    ops.push((true, left.start .. left.start+k, right.start .. right.start+k));
+                }
+                hunks.push(DiffHunk{ops: std::mem::take(&mut ops)});
+            }
+
+            if first || size > context * 2 {
+                // leading context for the next hunk
+                if !last {
+                    let k = size.min(context);
+                    if k > 0 {
+                        ops.push((true, left.end-k .. left.end, right.end-k .. right.end));
+                    }
+                }
+            } else if !last {
+                ops.push((true, left, right));
+            }
+        }
+
+        if ops.iter().any(|(matches, _, _)| !matches) {
+            hunks.push(DiffHunk{ops});
+        }
+        hunks
+    }
+}
@@ -0,0 +1,78 @@
+fn match_class(class: &[u8], c: u8) -> Option<(bool, usize)> {
+    // returns whether it matched and the length of the class
+    let (negate, start) = match class.first() {
+        Some(b'!' | b'^') => (true, 1),
+        _ => (false, 0),
+    };
+
+    let mut matched = false;
+    let mut i = start;
+    while i < class.len() {
+        // a ] at the very start is a literal
+        if class[i] == b']' && i > start {
+            return Some((matched != negate, i + 1))
+        }
+        if i + 2 < class.len() && class[i+1] == b'-' && class[i+2] != b']' {
+            matched = matched || (class[i] ..= class[i+2]).contains(&c);
+            i += 3;
+        } else {
+            matched = matched || class[i] == c;
+            i += 1;
+        }
+    }
+    // unterminated
+    None
+}
+
+pub fn glob_match(pattern: &[u8], string: &[u8]) -> bool {
+    match pattern {
+        [] => string.is_empty(),
+
+        // **/ matches zero or more directories
+        [b'*', b'*', b'/', rest @ ..] => {
+            glob_match(rest, string)
+            || string.iter().enumerate().any(|(i, &c)| c == b'/' && glob_match(rest, &string[i+1..]))
+        },
+        // ** matches across directories
+        [b'*', b'*', rest @ ..] => (0..=string.len()).any(|i| glob_match(rest, &string[i..])),
+        [b'*', rest @ ..] => {
+            let end = string.iter().position(|&c| c == b'/').unwrap_or(string.len());
+            (0..=end).any(|i| glob_match(rest, &string[i..]))
+        },
+
+        [b'?', rest @ ..] => matches!(string.first(), Some(&c) if c != b'/') && glob_match(rest, &string[1..]),
+
+        [b'[', class @ ..] if string.first().is_some_and(|&c| match_class(class, c).is_some()) => {
+            let (matched, len) = match_class(class, string[0]).unwrap();
+            matched && string[0] != b'/' && glob_match(&class[len..], &string[1..])
+        },
+
+        [b'\\', c, rest @ ..] | [c, rest @ ..] => string.first() == Some(c) && glob_match(rest, &string[1..]),
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct GlobFilter {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl GlobFilter {
+    fn matches(pattern: &str, path: &[u8]) -> bool {
+        // patterns without a slash only match the basename
+        let path = if pattern.contains('/') {
+            path
+        } else {
+            path.rsplit(|&c| c == b'/').next().unwrap_or(path)
+        };
+        glob_match(pattern.as_bytes(), path)
+    }
+
+    pub fn excluded(&self, path: &[u8]) -> bool {
+        self.exclude.iter().any(|p| Self::matches(p, path))
+    }
+
+    pub fn included(&self, path: &[u8]) -> bool {
+        self.include.is_empty() || self.include.iter().any(|p| Self::matches(p, path))
+    }
+}
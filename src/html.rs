@@ -0,0 +1,254 @@
+use std::io::{BufWriter, Write};
+use anyhow::Result;
+use bstr::BString;
+use super::printer::{Event, Printer, escape_with, trim_newline};
+use super::tokeniser::Tokeniser;
+use super::invisibles::{self, Kind, Show};
+use super::DiffBlock;
+
+const STYLESHEET: &str = "
+body { font-family: monospace; font-size: 13px; color: #1f2328; background: #ffffff; }
+.diff-header { font-weight: bold; margin-top: 1.5em; }
+.filename { margin: 0.5em 0; padding: 0.2em 0.5em; background: #f6f8fa; border: 1px solid #d0d7de; }
+.filename-left .non-matching { background: #ffcecb; }
+.filename-right .non-matching { background: #aceebb; }
+.filename-sign { color: #59636e; }
+.hunk { border-collapse: collapse; width: 100%; margin-bottom: 1em; }
+.hunk td { padding: 0 0.5em; vertical-align: top; }
+.hunk-header td { color: #59636e; background: #ddf4ff; }
+.hunk-header .context { font-weight: bold; color: #1f2328; }
+.lineno { color: #59636e; text-align: right; width: 1%; user-select: none; }
+.sign { width: 1%; user-select: none; }
+.line { white-space: pre-wrap; word-break: break-all; width: 100%; }
+.context .line { color: #59636e; }
+.deleted { background: #ffebe9; }
+.deleted .non-matching { background: #ffcecb; }
+.added { background: #e6ffec; }
+.added .non-matching { background: #aceebb; }
+.commit { font-weight: bold; background: #ddf4ff; margin-top: 1em; }
+.only-in-left { font-weight: bold; color: #d1242f; }
+.only-in-right { font-weight: bold; color: #1a7f37; }
+.text, .message { white-space: pre-wrap; }
+.invisible { color: #8c959f; }
+.bidi { color: #ffffff; background: #d1242f; font-weight: bold; }
+";
+
+pub fn escape(string: &[u8]) -> String {
+    escape_with(string, |c| match c {
+        '&' => Some("&amp;".into()),
+        '<' => Some("&lt;".into()),
+        '>' => Some("&gt;".into()),
+        '"' => Some("&quot;".into()),
+        _ => None,
+    })
+}
+
+// escapes the text, with glyphs for the invisible characters to show
+fn escape_invisibles(text: &[u8], show: Option<Show>) -> String {
+    invisibles::split(text, show).into_iter().map(|(piece, kind)| match kind {
+        Kind::Text => escape(piece),
+        Kind::Invisible => format!("<span class=\"invisible\">{}</span>", escape(piece)),
+        Kind::Bidi => format!("<span class=\"bidi\">{}</span>", escape(piece)),
+    }).collect()
+}
+
+// prints a self contained html document
+pub struct HtmlPrinter<T: Write> {
+    pub stdout: BufWriter<T>,
+    pub show_invisibles: bool,
+    started: bool,
+    in_table: bool,
+    // the last hunk header seen, as (header, context)
+    header: Option<(BString, BString)>,
+}
+
+impl<T: Write> HtmlPrinter<T> {
+    pub fn new(stdout: T) -> Self {
+        Self{
+            stdout: BufWriter::new(stdout),
+            show_invisibles: false,
+            started: false,
+            in_table: false,
+            header: None,
+        }
+    }
+
+    fn start(&mut self) -> Result<()> {
+        if !self.started {
+            self.started = true;
+            writeln!(self.stdout, "<!DOCTYPE html>")?;
+            writeln!(self.stdout, "<html>\n<head>\n<meta charset=\"utf-8\">\n<title>dyff</title>")?;
+            writeln!(self.stdout, "<style>{}</style>", STYLESHEET)?;
+            writeln!(self.stdout, "</head>\n<body>")?;
+        }
+        Ok(())
+    }
+
+    fn start_table(&mut self) -> Result<()> {
+        if !self.in_table {
+            self.in_table = true;
+            writeln!(self.stdout, "<table class=\"hunk\">")?;
+        }
+        if let Some((header, context)) = self.header.take() {
+            let context = if context.is_empty() {
+                String::new()
+            } else {
+                format!(" <span class=\"context\">{}</span>", escape(&context))
+            };
+            writeln!(self.stdout, "<tr class=\"hunk-header\"><td colspan=\"4\">{}{}</td></tr>", escape(&header), context)?;
+        }
+        Ok(())
+    }
+
+    fn end_table(&mut self) -> Result<()> {
+        if self.in_table {
+            self.in_table = false;
+            writeln!(self.stdout, "</table>")?;
+        }
+        Ok(())
+    }
+
+    fn print_div(&mut self, class: &str, text: &[u8]) -> Result<()> {
+        self.end_table()?;
+        let text = super::style::strip_style(text.into(), b"");
+        writeln!(self.stdout, "<div class=\"{}\">{}</div>", class, escape(trim_newline(&text)))?;
+        Ok(())
+    }
+
+    fn print_row(&mut self, class: &str, line_numbers: [Option<usize>; 2], line: &str) -> Result<()> {
+        let sign = match class {
+            "deleted" => "-",
+            "added" => "+",
+            _ => " ",
+        };
+        let [left, right] = line_numbers.map(|n| n.map(|n| n.to_string()).unwrap_or_default());
+        writeln!(
+            self.stdout,
+            "<tr class=\"{}\"><td class=\"lineno\">{}</td><td class=\"lineno\">{}</td><td class=\"sign\">{}</td><td class=\"line\">{}</td></tr>",
+            class, left, right, sign, line,
+        )?;
+        Ok(())
+    }
+
+    fn print_block(&mut self, block: &DiffBlock) -> Result<()> {
+        if block.parts.iter().all(|p| p.matches) {
+            // same on both sides, so print it once
+            let Some(first) = block.parts.first() else { return Ok(()) };
+            let mut line_numbers = [first.left.first_lineno, first.right.first_lineno];
+            let text: BString = block.parts.iter().flat_map(|p| p.left.text.iter().copied()).collect();
+            let show = Show::new(self.show_invisibles, false);
+            for line in text.split_inclusive(|&c| c == b'\n') {
+                self.print_row("context", line_numbers.map(Some), &escape_invisibles(trim_newline(line), show))?;
+                line_numbers = line_numbers.map(|n| n + 1);
+            }
+            return Ok(())
+        }
+
+        let show = Show::new(self.show_invisibles, true);
+        for (i, class) in [(0, "deleted"), (1, "added")] {
+            let mut lines = vec![];
+            let mut line = String::new();
+            let mut lineno = block.parts.first().map_or(0, |p| p.get(i).first_lineno);
+            for part in block.parts.iter() {
+                let side = part.get(i);
+                for word in side.text.split_inclusive(|&c| c == b'\n') {
+                    let text = escape_invisibles(trim_newline(word), show);
+                    if part.matches || text.is_empty() {
+                        line.push_str(&text);
+                    } else {
+                        line.push_str(&format!("<span class=\"non-matching\">{}</span>", text));
+                    }
+                    if word.ends_with(b"\n") {
+                        lines.push((lineno, std::mem::take(&mut line)));
+                        lineno += 1;
+                    }
+                }
+            }
+            if !line.is_empty() {
+                // no newline at end of file
+                lines.push((lineno, line));
+            }
+
+            for (lineno, line) in lines {
+                let line_numbers = if i == 0 { [Some(lineno), None] } else { [None, Some(lineno)] };
+                self.print_row(class, line_numbers, &line)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T: Write> Printer for HtmlPrinter<T> {
+    fn print(&mut self, tokeniser: &mut Tokeniser, event: Event) -> Result<()> {
+        self.start()?;
+
+        match event {
+            Event::DiffHeader{header, filename1, filename2, trailer} => {
+                self.end_table()?;
+                writeln!(
+                    self.stdout,
+                    "<div class=\"diff-header\">{} {} {}{}</div>",
+                    escape(header), escape(filename1), escape(filename2), escape(trailer),
+                )?;
+            },
+            Event::Filename{left, right, rename, ..} => {
+                self.end_table()?;
+                let (prefix_left, prefix_right) = if rename { ("rename from ", "rename to ") } else { ("--- ", "+++ ") };
+                let blocks = super::diff_texts(
+                    left.map_or(b"".as_slice(), |f| trim_newline(f)),
+                    right.map_or(b"".as_slice(), |f| trim_newline(f)),
+                );
+                let filename = |i: usize| -> String {
+                    blocks.iter().flat_map(|b| b.parts.iter()).map(|p| {
+                        let text = escape(&p.get(i).text);
+                        if p.matches || text.is_empty() { text } else { format!("<span class=\"non-matching\">{}</span>", text) }
+                    }).collect()
+                };
+                writeln!(
+                    self.stdout,
+                    "<div class=\"filename\"><div class=\"filename-left\"><span class=\"filename-sign\">{}</span>{}</div><div class=\"filename-right\"><span class=\"filename-sign\">{}</span>{}</div></div>",
+                    prefix_left, filename(0), prefix_right, filename(1),
+                )?;
+            },
+            Event::HunkHeader{header, context} | Event::MergeHunkHeader{header, context} => {
+                self.end_table()?;
+                self.header = Some((header.into(), context.into()));
+            },
+            Event::NormalHunkHeader{line} => {
+                self.end_table()?;
+                self.header = Some((trim_newline(line).into(), BString::default()));
+            },
+            Event::Hunk{hunk, line_numbers, merge_markers: _} => {
+                if !hunk.is_empty() {
+                    self.start_table()?;
+                    for block in super::diff_lines_with_words(&hunk.left, &hunk.right, line_numbers, tokeniser.current_words().clone()) {
+                        self.print_block(&block)?;
+                    }
+                }
+            },
+            Event::Context{line_numbers, line} => {
+                self.start_table()?;
+                let show = Show::new(self.show_invisibles, false);
+                self.print_row("context", line_numbers.map(Some), &escape_invisibles(trim_newline(line), show))?;
+            },
+            Event::Commit{line} => self.print_div("commit", line)?,
+            Event::OnlyIn{side, message} => self.print_div(if side == 0 { "only-in-left" } else { "only-in-right" }, message)?,
+            Event::Message{message} => self.print_div("message", message)?,
+            Event::Index{line} => self.print_div("text", line)?,
+            Event::Text{line} | Event::Raw{line} => {
+                if !trim_newline(line).is_empty() {
+                    self.print_div("text", line)?;
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, _tokeniser: &mut Tokeniser) -> Result<()> {
+        self.start()?;
+        self.end_table()?;
+        writeln!(self.stdout, "</body>\n</html>")?;
+        self.stdout.flush()?;
+        Ok(())
+    }
+}
@@ -1,16 +1,27 @@
+#[cfg(feature = "cli")]
 use std::collections::HashMap;
+#[cfg(feature = "cli")]
 use std::io::{BufWriter, Write};
+#[cfg(feature = "cli")]
 use anyhow::{Result};
+#[cfg(feature = "cli")]
 use super::style::Style;
 use super::types::*;
+#[cfg(feature = "cli")]
 use super::block_maker::BlockMaker;
+#[cfg(feature = "cli")]
+use super::block::Block;
 
+#[cfg(feature = "cli")]
 pub type MergeMarkers = HashMap<(usize, usize), String>;
 
-#[derive(Debug)]
+#[derive(Debug, Default, Clone)]
 pub struct Hunk {
     pub left: Line,
     pub right: Line,
+    // normal diffs have a --- line between the two sides
+    #[cfg(feature = "cli")]
+    pub separator: bool,
 }
 
 impl Hunk {
@@ -18,6 +29,8 @@ impl Hunk {
         Self{
             left: vec![],
             right: vec![],
+            #[cfg(feature = "cli")]
+            separator: false,
         }
     }
 
@@ -25,13 +38,16 @@ impl Hunk {
         if i == 0 { &self.left } else { &self.right }
     }
 
-    pub fn get_mut(&mut self, i: usize) -> &mut Line {
-        if i == 0 { &mut self.left } else { &mut self.right }
-    }
-
     pub fn is_empty(&self) -> bool {
         self.left.is_empty() && self.right.is_empty()
     }
+}
+
+#[cfg(feature = "cli")]
+impl Hunk {
+    pub fn get_mut(&mut self, i: usize) -> &mut Line {
+        if i == 0 { &mut self.left } else { &mut self.right }
+    }
 
     pub fn print<T: std::io::Write>(
         &mut self,
@@ -40,13 +56,23 @@ impl Hunk {
         line_numbers: [usize; 2],
         merge_markers: Option<&MergeMarkers>,
         style: Style,
-        style_opts: &super::StyleOpts,
+        style_opts: &super::style::StyleOpts,
     ) -> Result<()> {
 
+        if style.preserve_lines {
+            return self.print_preserving_lines(stdout, tokeniser, line_numbers, merge_markers, style, style_opts)
+        }
+
         if !self.is_empty() {
 
             let maker = BlockMaker::new(self, line_numbers, tokeniser);
-            let blocks = maker.make_block().split_block();
+            let mut blocks = maker.make_block().split_block();
+            if style.ignore_space.is_some() {
+                blocks = blocks.into_iter().flat_map(|b| b.split_ignored(style)).collect();
+            }
+            if style.reindent && style.side_by_side.is_none() {
+                blocks = blocks.into_iter().flat_map(Block::split_reindented).collect();
+            }
 
             let len = blocks.len();
             let last = [0, 1].map(|i| {
@@ -54,22 +80,71 @@ impl Hunk {
             });
 
             for (i, block) in blocks.iter().enumerate() {
-                block.print(stdout, merge_markers, style, style_opts, i == last[0] || i == last[1], super::style::format_lineno)?;
+                block.print(stdout, merge_markers, style, style_opts, i == last[0] || i == last[1], |num, left, right, bar| {
+                    super::style::format_lineno(num, left, right, bar, style.lineno_width, style.hyperlink)
+                })?;
                 stdout.flush()?;
             }
         }
         Ok(())
     }
 
-    pub fn print_filename<'a, T: std::io::Write>(
+    // prints every line of the left side and then every line of the right side,
+    // so that the output lines up with the input
+    fn print_preserving_lines<T: std::io::Write>(
+        &self,
         stdout: &mut BufWriter<T>,
         tokeniser: &mut super::tokeniser::Tokeniser,
+        line_numbers: [usize; 2],
+        merge_markers: Option<&MergeMarkers>,
+        style: Style,
+        style_opts: &super::style::StyleOpts,
+    ) -> Result<()> {
+
+        let maker = BlockMaker::new(self, line_numbers, tokeniser);
+        let blocks = if self.is_empty() { vec![] } else { maker.make_block().split_block() };
+        let last = [0, 1].map(|i| blocks.iter().rposition(|b| !b.is_empty(i)));
+
+        // check the line count before anything is written, so git never sees a broken hunk
+        let mut output = BufWriter::new(vec![]);
+        for (side, last) in last.into_iter().enumerate() {
+            if side == 1 && self.separator {
+                output.write_all(style_opts.diff_context.as_bytes())?;
+                output.write_all(b"---")?;
+                output.write_all(super::style::RESET)?;
+                output.write_all(b"\n")?;
+            }
+
+            // never merge lines, even if they are the same on both sides
+            let style = Style{show_both: true, inline: false, side: Some(side), ..style};
+            for (i, block) in blocks.iter().enumerate() {
+                block.print(&mut output, merge_markers, style, style_opts, Some(i) == last, |num, left, right, bar| {
+                    super::style::format_lineno(num, left, right, bar, style.lineno_width, style.hyperlink)
+                })?;
+            }
+        }
+        let output = output.into_inner()?;
+
+        // lines without a newline are followed by a \ No newline at end of file line
+        let lines = [&self.left, &self.right].iter().flat_map(|side| side.iter()).map(|l| if l.ends_with(b"\n") { 1 } else { 2 }).sum::<usize>();
+        let expected = lines + self.separator as usize;
+        let printed = output.split_inclusive(|&c| c == b'\n').count();
+        if printed != expected {
+            anyhow::bail!("--preserve-lines: hunk at line {} has {expected} lines but would print {printed}, please report this as a bug", line_numbers[0]);
+        }
+        stdout.write_all(&output)?;
+        stdout.flush()?;
+        Ok(())
+    }
+
+    pub fn print_filename<'a, T: std::io::Write>(
+        stdout: &mut BufWriter<T>,
         left: Option<Bytes>,
         right: Option<Bytes>,
         prefix: (&'a str, &'a str, &'a str),
         // suffix: (&'a str, &'a str),
         style: Style,
-        style_opts: &super::StyleOpts,
+        style_opts: &super::style::StyleOpts,
     ) -> Result<()> {
 
         let mut hunk = Self::new();
@@ -82,27 +157,76 @@ impl Hunk {
             hunk.get_mut(i).push(filename);
         }
 
-        let style = Style{
+        let filename_style = Style{
             signs: false,
             line_numbers: true,
             show_both: true,
+            side_by_side: None,
             // inline: false,
             diff_matching: [(*style_opts.filename_header_left).into(), (*style_opts.filename_header_right).into()],
             diff_matching_inline: (*style_opts.filename_rename).into(),
             diff_non_matching: [(*style_opts.filename_non_matching_left).into(), (*style_opts.filename_non_matching_right).into()],
+            syntax: None,
+            hyperlink: None,
             ..style
         };
-        let maker = BlockMaker::new(&hunk, [1, 1], tokeniser);
+        // link the new file name to the file
+        let link = style.hyperlink.map(|url| super::hyperlink::start(url, 1)).unwrap_or_default();
+
+        // file names are split as code whatever the words are for the file
+        let mut tokeniser = super::tokeniser::Tokeniser::new();
+        let maker = BlockMaker::new(&hunk, [1, 1], &mut tokeniser);
         let blocks = maker.make_block().split_block();
         for block in blocks {
-            block.print(stdout, None, style, style_opts, false, |num: [usize; 2], _, _, _| -> &'a str {
+            block.print(stdout, None, filename_style, style_opts, false, |num: [usize; 2], _, _, _| -> String {
                 match num {
-                    [_, 0] => prefix.0,
-                    [0, _] => prefix.1,
-                    [_, _] => prefix.2,
+                    [_, 0] => prefix.0.to_owned(),
+                    [0, _] => format!("{}{}", prefix.1, link),
+                    [_, _] => format!("{}{}", prefix.2, link),
                 }
             })?;
         }
+        if !link.is_empty() {
+            stdout.write_all(super::hyperlink::END.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn print_header<T: Write>(stdout: &mut BufWriter<T>, style_opts: &super::style::StyleOpts, header: &[u8], context: &[u8]) -> Result<()> {
+        style_opts.print_background(stdout)?;
+        stdout.write_all(style_opts.header.as_bytes())?;
+        stdout.write_all(header)?;
+        if !context.is_empty() {
+            stdout.write_all(b" ")?;
+            stdout.write_all(style_opts.context.as_bytes())?;
+            stdout.write_all(context)?;
+        }
+        stdout.write_all(super::style::RESET)?;
+        stdout.write_all(b"\n")?;
+        Ok(())
+    }
+
+    pub fn print_diff_header<T: Write>(
+        stdout: &mut BufWriter<T>,
+        style_opts: &super::style::StyleOpts,
+        header: &[u8],
+        filename1: &[u8],
+        filename2: &[u8],
+        trailer: &[u8],
+    ) -> Result<()> {
+        style_opts.print_background(stdout)?;
+        stdout.write_all(super::style::DIFF_HEADER.as_bytes())?;
+        stdout.write_all(header)?;
+        stdout.write_all(b" ")?;
+        stdout.write_all(super::style::RESET)?;
+        stdout.write_all(style_opts.filename_header_left.as_bytes())?;
+        stdout.write_all(filename1)?;
+        stdout.write_all(b" ")?;
+        stdout.write_all(style_opts.filename_header_right.as_bytes())?;
+        stdout.write_all(filename2)?;
+        stdout.write_all(super::style::RESET)?;
+        stdout.write_all(trailer)?;
+        stdout.write_all(b"\n")?;
         Ok(())
     }
 
@@ -0,0 +1,63 @@
+use std::cell::OnceCell;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use bstr::ByteSlice;
+
+pub const END: &str = "\x1b]8;;\x1b\\";
+
+// makes osc 8 hyperlinks to files from a template like vscode://file/{path}:{line}
+#[derive(Debug, Clone)]
+pub struct Hyperlinks {
+    pub template: String,
+    // relative file names in the diff are relative to this
+    pub root: PathBuf,
+    // except in git diffs, where they are relative to the top of the repo, which is looked up when first needed
+    git_root: OnceCell<PathBuf>,
+}
+
+fn percent_encode(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for c in path.bytes() {
+        if c.is_ascii_alphanumeric() || b"/-_.~:".contains(&c) {
+            encoded.push(c as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", c));
+        }
+    }
+    encoded
+}
+
+impl Hyperlinks {
+    pub fn new(template: String, root: PathBuf) -> Self {
+        Self{template, root, git_root: OnceCell::new()}
+    }
+
+    fn git_root(&self) -> &Path {
+        self.git_root.get_or_init(|| {
+            match Command::new("git").args(["rev-parse", "--show-toplevel"]).stderr(Stdio::null()).output() {
+                Ok(output) if output.status.success() => {
+                    PathBuf::from(String::from_utf8_lossy(&output.stdout).trim_end_matches('\n'))
+                },
+                // not in a repo, so the best guess is still where we are
+                _ => self.root.clone(),
+            }
+        })
+    }
+
+    // the url for a file name from the diff, with {line} left in for the line numbers
+    pub fn file_url(&self, filename: &[u8], git: bool) -> Option<String> {
+        let filename = filename.split(|&c| c == b'\t').next().unwrap_or(filename).trim_ascii();
+        let filename = filename.strip_prefix(b"\"").and_then(|f| f.strip_suffix(b"\"")).unwrap_or(filename);
+        if filename.is_empty() || filename == b"/dev/null" {
+            return None
+        }
+        let root = if git { self.git_root() } else { &self.root };
+        let path = root.join(filename.to_str_lossy().as_ref());
+        Some(self.template.replace("{path}", &percent_encode(&path.to_string_lossy())))
+    }
+}
+
+// the start of a link, to be closed with END
+pub fn start(url: &str, line: usize) -> String {
+    format!("\x1b]8;;{}\x1b\\", url.replace("{line}", &line.to_string()))
+}
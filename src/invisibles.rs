@@ -0,0 +1,139 @@
+use std::borrow::Cow;
+use bstr::ByteSlice;
+use super::style::StyleOpts;
+
+// which characters to replace with glyphs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Show {
+    // only bidi controls
+    Bidi,
+    All,
+}
+
+impl Show {
+    // bidi controls are always shown in changed lines, since they can make code read differently to how it runs
+    pub fn new(all: bool, changed: bool) -> Option<Self> {
+        if all {
+            Some(Self::All)
+        } else if changed {
+            Some(Self::Bidi)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Text,
+    Invisible,
+    Bidi,
+}
+
+impl Kind {
+    pub fn style(self, style_opts: &StyleOpts) -> &str {
+        match self {
+            Self::Text => "",
+            Self::Invisible => &style_opts.diff_invisible,
+            Self::Bidi => &style_opts.diff_bidi,
+        }
+    }
+}
+
+fn glyph(c: char) -> Option<(&'static str, Kind)> {
+    let glyph = match c {
+        '\u{061c}' => "<ALM>",
+        '\u{200e}' => "<LRM>",
+        '\u{200f}' => "<RLM>",
+        '\u{202a}' => "<LRE>",
+        '\u{202b}' => "<RLE>",
+        '\u{202c}' => "<PDF>",
+        '\u{202d}' => "<LRO>",
+        '\u{202e}' => "<RLO>",
+        '\u{2066}' => "<LRI>",
+        '\u{2067}' => "<RLI>",
+        '\u{2068}' => "<FSI>",
+        '\u{2069}' => "<PDI>",
+        _ => return invisible_glyph(c).map(|g| (g, Kind::Invisible)),
+    };
+    Some((glyph, Kind::Bidi))
+}
+
+fn invisible_glyph(c: char) -> Option<&'static str> {
+    Some(match c {
+        // keep the tab so that the text after it still lines up
+        '\t' => "→\t",
+        '\r' => "␍",
+        '\x0c' => "␌",
+        '\x0b' => "␋",
+        '\0' => "␀",
+        '\x07' => "␇",
+        '\x08' => "␈",
+        '\x1b' => "␛",
+        '\x7f' => "␡",
+        '\u{a0}' => "␣",
+        '\u{200b}' => "<ZWSP>",
+        '\u{2060}' => "<WJ>",
+        '\u{feff}' => "<BOM>",
+        _ => return None,
+    })
+}
+
+// whether the character is one that can be shown with a glyph
+pub fn has_glyph(c: char) -> bool {
+    glyph(c).is_some()
+}
+
+// splits text into runs of text and glyphs for the characters to show
+pub fn split(text: &[u8], show: Option<Show>) -> Vec<(&[u8], Kind)> {
+    let Some(show) = show else {
+        return vec![(text, Kind::Text)]
+    };
+
+    let mut pieces = vec![];
+    let mut start = 0;
+    for (i, end, c) in text.char_indices() {
+        if let Some((glyph, kind)) = glyph(c)
+            && (show == Show::All || kind == Kind::Bidi)
+        {
+            if start < i {
+                pieces.push((&text[start..i], Kind::Text));
+            }
+            pieces.push((glyph.as_bytes(), kind));
+            start = end;
+        }
+    }
+    if start < text.len() || pieces.is_empty() {
+        pieces.push((&text[start..], Kind::Text));
+    }
+    pieces
+}
+
+// replaces the characters to show with glyphs, going back to the restore style after each one
+pub fn replace<'a>(text: &'a [u8], show: Option<Show>, style_opts: &StyleOpts, restore: &[&[u8]]) -> Cow<'a, [u8]> {
+    let pieces = split(text, show);
+    if let [(text, Kind::Text)] = pieces[..] {
+        return Cow::Borrowed(text)
+    }
+
+    let mut buf = vec![];
+    for (piece, kind) in pieces {
+        if kind == Kind::Text {
+            buf.extend_from_slice(piece);
+        } else {
+            buf.extend_from_slice(kind.style(style_opts).as_bytes());
+            buf.extend_from_slice(piece);
+            restore.iter().for_each(|r| buf.extend_from_slice(r));
+        }
+    }
+    Cow::Owned(buf)
+}
+
+// the same without colour, for plain text output
+pub fn replace_plain(text: &[u8], show: Option<Show>) -> Cow<'_, [u8]> {
+    let pieces = split(text, show);
+    if let [(text, Kind::Text)] = pieces[..] {
+        return Cow::Borrowed(text)
+    }
+    Cow::Owned(pieces.into_iter().flat_map(|(piece, _)| piece.iter().copied()).collect())
+}
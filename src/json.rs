@@ -0,0 +1,124 @@
+use std::io::{BufWriter, Write};
+use anyhow::Result;
+use bstr::BString;
+use super::printer::{Event, Printer, escape_with, trim_newline};
+use super::tokeniser::Tokeniser;
+use super::{DiffBlock, DiffSide};
+
+pub fn escape(string: &[u8]) -> String {
+    let escaped = escape_with(string, |c| match c {
+        '"' => Some("\\\"".into()),
+        '\\' => Some("\\\\".into()),
+        '\n' => Some("\\n".into()),
+        '\r' => Some("\\r".into()),
+        '\t' => Some("\\t".into()),
+        // bidi controls and other invisible characters are escaped so they cannot reorder the text when it is shown
+        c if (c as u32) < 0x20 || super::invisibles::has_glyph(c) => Some(format!("\\u{:04x}", c as u32).into()),
+        _ => None,
+    });
+    format!("\"{escaped}\"")
+}
+
+fn side_to_json(side: &DiffSide) -> String {
+    format!(
+        r#"{{"text":{},"first_lineno":{},"last_lineno":{}}}"#,
+        escape(&side.text), side.first_lineno, side.last_lineno,
+    )
+}
+
+fn block_to_json(block: &DiffBlock) -> String {
+    let parts: Vec<_> = block.parts.iter().map(|part| format!(
+        r#"{{"matches":{},"left":{},"right":{}}}"#,
+        part.matches, side_to_json(&part.left), side_to_json(&part.right),
+    )).collect();
+    format!(r#"{{"score":{},"parts":[{}]}}"#, block.score, parts.join(","))
+}
+
+// prints one json object per line
+pub struct JsonPrinter<T: Write> {
+    pub stdout: BufWriter<T>,
+    // the last hunk header seen, as (header, context)
+    header: (BString, BString),
+}
+
+impl<T: Write> JsonPrinter<T> {
+    pub fn new(stdout: T) -> Self {
+        Self{
+            stdout: BufWriter::new(stdout),
+            header: Default::default(),
+        }
+    }
+
+    fn print_text(&mut self, kind: &str, text: &[u8]) -> Result<()> {
+        let text = super::style::strip_style(text.into(), b"");
+        let text = trim_newline(&text);
+        if !text.is_empty() {
+            writeln!(self.stdout, r#"{{"type":"{}","text":{}}}"#, kind, escape(text))?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: Write> Printer for JsonPrinter<T> {
+    fn print(&mut self, tokeniser: &mut Tokeniser, event: Event) -> Result<()> {
+        match event {
+            Event::DiffHeader{header, filename1, filename2, trailer: _} => {
+                writeln!(
+                    self.stdout,
+                    r#"{{"type":"diff","header":{},"filename1":{},"filename2":{}}}"#,
+                    escape(header), escape(filename1), escape(filename2),
+                )?;
+            },
+            Event::Filename{left, right, rename, ..} => {
+                let filename = |f: Option<&bstr::BStr>| f.map_or("null".into(), |f| escape(trim_newline(f)));
+                writeln!(
+                    self.stdout,
+                    r#"{{"type":"file","left":{},"right":{},"rename":{}}}"#,
+                    filename(left), filename(right), rename,
+                )?;
+            },
+            Event::HunkHeader{header, context} | Event::MergeHunkHeader{header, context} => {
+                self.header = (header.into(), context.into());
+            },
+            Event::NormalHunkHeader{line} => {
+                self.header = (trim_newline(line).into(), BString::default());
+            },
+            Event::Hunk{hunk, line_numbers, merge_markers: _} => {
+                if !hunk.is_empty() {
+                    let blocks: Vec<_> = super::diff_lines_with_words(&hunk.left, &hunk.right, line_numbers, tokeniser.current_words().clone()).iter().map(block_to_json).collect();
+                    writeln!(
+                        self.stdout,
+                        r#"{{"type":"hunk","header":{},"context":{},"line_numbers":[{},{}],"blocks":[{}]}}"#,
+                        escape(&self.header.0), escape(&self.header.1),
+                        line_numbers[0], line_numbers[1],
+                        blocks.join(","),
+                    )?;
+                }
+            },
+            Event::Context{line_numbers, line} => {
+                writeln!(
+                    self.stdout,
+                    r#"{{"type":"context","line_numbers":[{},{}],"text":{}}}"#,
+                    line_numbers[0], line_numbers[1], escape(trim_newline(line)),
+                )?;
+            },
+            Event::Commit{line} => self.print_text("commit", line)?,
+            Event::OnlyIn{side, message} => {
+                writeln!(
+                    self.stdout,
+                    r#"{{"type":"only_in","side":"{}","text":{}}}"#,
+                    if side == 0 { "left" } else { "right" }, escape(message),
+                )?;
+            },
+            Event::Message{message} => self.print_text("message", message)?,
+            Event::Index{line} => self.print_text("text", line)?,
+            Event::Text{line} | Event::Raw{line} => self.print_text("text", line)?,
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, _tokeniser: &mut Tokeniser) -> Result<()> {
+        self.stdout.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,236 @@
+//! Word level diffing of hunks, as used by the `dyff` command.
+//!
+//! ```
+//! let blocks = dyff::diff_texts(b"let x = 1;\n", b"let y = 1;\n");
+//! let changed: Vec<_> = blocks[0].parts.iter().filter(|p| !p.matches).map(|p| &p.left.text).collect();
+//! assert_eq!(changed, ["x"]);
+//! ```
+
+use std::ops::Range;
+use bstr::BString;
+
+#[macro_use]
+mod regexes;
+mod block_maker;
+mod part;
+mod word_differ;
+mod line_differ;
+mod block;
+mod whitespace;
+mod shift;
+#[cfg(feature = "cli")]
+mod file_differ;
+#[cfg(feature = "cli")]
+mod char_differ;
+#[cfg(feature = "cli")]
+mod style_parser;
+#[cfg(feature = "cli")]
+mod invisibles;
+
+// internals the command line tool is built from, only public with the `cli` feature
+macro_rules! cli_mods {
+    ($($name:ident),*) => {$(
+        #[cfg(feature = "cli")]
+        #[doc(hidden)]
+        pub mod $name;
+        #[cfg(not(feature = "cli"))]
+        mod $name;
+    )*};
+}
+cli_mods!(hunk, types, tokeniser);
+
+// the printing code, only built for the command line tool
+macro_rules! cli_only_mods {
+    ($($name:ident),*) => {$(
+        #[cfg(feature = "cli")]
+        #[doc(hidden)]
+        pub mod $name;
+    )*};
+}
+cli_only_mods!(style, builtin, glob, terminal, printer, json, html, theme, color_depth, moved, syntax, markers, hyperlink, stat);
+
+use hunk::Hunk;
+use block_maker::BlockMaker;
+use tokeniser::Tokeniser;
+pub use tokeniser::Words;
+
+/// One side of a [`DiffPart`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffSide {
+    pub text: BString,
+    /// Byte range of `text` within this side of the input.
+    pub range: Range<usize>,
+    /// Line numbers of the first and last line that `text` is on.
+    /// For an empty side, this is the line the text would be inserted at.
+    pub first_lineno: usize,
+    pub last_lineno: usize,
+}
+
+/// A run of words that either matches on both sides or differs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffPart {
+    pub matches: bool,
+    pub left: DiffSide,
+    pub right: DiffSide,
+}
+
+impl DiffPart {
+    pub fn get(&self, i: usize) -> &DiffSide {
+        if i == 0 { &self.left } else { &self.right }
+    }
+}
+
+/// A group of lines that are diffed against each other.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffBlock {
+    /// How similar the two sides are, from 0 (nothing in common) to 1 (identical).
+    pub score: f64,
+    pub parts: Vec<DiffPart>,
+}
+
+/// Diffs the lines of `left` against the lines of `right`.
+/// Line numbers start from 1.
+pub fn diff_texts(left: &[u8], right: &[u8]) -> Vec<DiffBlock> {
+    let [left, right] = [left, right].map(|text| text.split_inclusive(|&c| c == b'\n').collect::<Vec<_>>());
+    diff_lines(&left, &right, [1, 1])
+}
+
+/// Diffs `left` against `right`, numbering their lines from `line_numbers`.
+/// Each line should end with its newline, if it has one.
+/// Byte ranges are relative to the start of the first line.
+pub fn diff_lines<L: AsRef<[u8]>>(left: &[L], right: &[L], line_numbers: [usize; 2]) -> Vec<DiffBlock> {
+    diff_lines_with_words(left, right, line_numbers, Words::default())
+}
+
+/// Like [`diff_lines`], but splitting lines into `words`,
+/// e.g. from [`Words::from_filename`] to split them like the `dyff` command does.
+pub fn diff_lines_with_words<L: AsRef<[u8]>>(left: &[L], right: &[L], line_numbers: [usize; 2], words: Words) -> Vec<DiffBlock> {
+    let mut hunk = Hunk::new();
+    hunk.left = left.iter().map(|l| l.as_ref().into()).collect();
+    hunk.right = right.iter().map(|l| l.as_ref().into()).collect();
+    diff_hunk(&hunk, line_numbers, &mut Tokeniser::with_words(Some(words)))
+}
+
+fn diff_hunk(hunk: &Hunk, line_numbers: [usize; 2], tokeniser: &mut Tokeniser) -> Vec<DiffBlock> {
+    if hunk.is_empty() {
+        return vec![]
+    }
+
+    let maker = BlockMaker::new(hunk, line_numbers, tokeniser);
+
+    let texts = [0, 1].map(|i| hunk.get(i).concat());
+    // byte offset of the start and end of each word
+    let offsets = [0, 1].map(|i| {
+        let mut offsets = vec![];
+        let mut line_start = 0;
+        for (lineno, line) in hunk.get(i).iter().enumerate() {
+            let words = &maker.words[i][maker.line_to_word[i][lineno] .. maker.line_to_word[i][lineno + 1]];
+            for word in words {
+                let start = line_start + (word.as_ptr() as usize - line.as_ptr() as usize);
+                offsets.push(start .. start + word.len());
+            }
+            line_start += line.len();
+        }
+        offsets
+    });
+
+    maker.make_block().split_block().iter().map(|block| DiffBlock{
+        score: block.score(),
+        parts: block.parts.iter().map(|part| {
+            let [left, right] = [0, 1].map(|i| {
+                let slice = &part.slices[i];
+                let range = if slice.is_empty() {
+                    let pos = offsets[i].get(slice.start).map_or(texts[i].len(), |r| r.start);
+                    pos .. pos
+                } else {
+                    offsets[i][slice.start].start .. offsets[i][slice.end - 1].end
+                };
+                let mut lines = [part.first_lineno(i), part.last_lineno(i)];
+                // anything added after a last line without a newline goes on the end of that line
+                if slice.is_empty() && range.start == texts[i].len() && !texts[i].is_empty() && !texts[i].ends_with(b"\n") {
+                    lines = lines.map(|n| n - 1);
+                }
+                DiffSide{
+                    text: texts[i][range.clone()].into(),
+                    range,
+                    first_lineno: lines[0],
+                    last_lineno: lines[1],
+                }
+            });
+            DiffPart{matches: part.matches, left, right}
+        }).collect(),
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn side(text: &str, range: Range<usize>, lineno: usize) -> DiffSide {
+        DiffSide{text: text.into(), range, first_lineno: lineno, last_lineno: lineno}
+    }
+
+    fn part(matches: bool, left: DiffSide, right: DiffSide) -> DiffPart {
+        DiffPart{matches, left, right}
+    }
+
+    fn parts(blocks: &[DiffBlock]) -> Vec<DiffPart> {
+        blocks.iter().flat_map(|b| b.parts.clone()).collect()
+    }
+
+    #[test]
+    fn insert() {
+        assert_eq!(parts(&diff_texts(b"a\nb\n", b"a\nx\nb\n")), [
+            part(true, side("a\n", 0..2, 1), side("a\n", 0..2, 1)),
+            part(false, side("", 2..2, 2), side("x\n", 2..4, 2)),
+            part(true, side("b\n", 2..4, 2), side("b\n", 4..6, 3)),
+        ]);
+    }
+
+    #[test]
+    fn delete() {
+        assert_eq!(parts(&diff_texts(b"a\nx\ny\nb\n", b"a\nb\n")), [
+            part(true, side("a\n", 0..2, 1), side("a\n", 0..2, 1)),
+            part(false, DiffSide{text: "x\ny\n".into(), range: 2..6, first_lineno: 2, last_lineno: 3}, side("", 2..2, 2)),
+            part(true, side("b\n", 6..8, 4), side("b\n", 2..4, 2)),
+        ]);
+    }
+
+    #[test]
+    fn insert_at_end() {
+        assert_eq!(parts(&diff_texts(b"a\n", b"a\nb\n")), [
+            part(true, side("a\n", 0..2, 1), side("a\n", 0..2, 1)),
+            part(false, side("", 2..2, 2), side("b\n", 2..4, 2)),
+        ]);
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(diff_texts(b"", b""), []);
+        assert_eq!(parts(&diff_texts(b"", b"a\n")), [part(false, side("", 0..0, 1), side("a\n", 0..2, 1))]);
+        assert_eq!(parts(&diff_texts(b"a\n", b"")), [part(false, side("a\n", 0..2, 1), side("", 0..0, 1))]);
+    }
+
+    #[test]
+    fn no_trailing_newline() {
+        assert_eq!(parts(&diff_texts(b"a\nb", b"a\nb\n")), [
+            part(true, side("a\n", 0..2, 1), side("a\n", 0..2, 1)),
+            part(true, side("b", 2..3, 2), side("b", 2..3, 2)),
+            part(false, side("", 3..3, 2), side("\n", 3..4, 2)),
+        ]);
+        assert_eq!(parts(&diff_texts(b"x = 1", b"y = 1")), [
+            part(false, side("x", 0..1, 1), side("y", 0..1, 1)),
+            part(true, side(" = 1", 1..5, 1), side(" = 1", 1..5, 1)),
+        ]);
+    }
+
+    #[test]
+    fn line_numbers() {
+        let blocks = diff_lines(&["x = 1\n", "z\n"], &["y = 1\n", "z\n"], [10, 20]);
+        assert_eq!(parts(&blocks), [
+            part(false, side("x", 0..1, 10), side("y", 0..1, 20)),
+            part(true, side(" = 1\n", 1..6, 10), side(" = 1\n", 1..6, 20)),
+            part(true, side("z\n", 6..8, 11), side("z\n", 6..8, 21)),
+        ]);
+    }
+}
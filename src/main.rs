@@ -1,32 +1,29 @@
-use std::io::{BufRead, BufReader, BufWriter, Write, IsTerminal};
+use std::cell::Cell;
+use std::io::{BufRead, BufReader, IsTerminal};
+use std::rc::Rc;
 use std::process::{Command, Stdio, ExitCode};
 use std::collections::HashMap;
-use clap::Parser;
-use anyhow::{Result};
-use std::borrow::Cow;
-
-mod hunk;
-mod style;
-mod block_maker;
-mod part;
-mod word_differ;
-mod line_differ;
-mod block;
-mod types;
-mod whitespace;
-mod shift;
-mod tokeniser;
-#[macro_use]
+use std::ffi::OsString;
+use std::path::PathBuf;
+use clap::{CommandFactory, FromArgMatches};
+use anyhow::{Context, Result};
+
+use dyff::{builtin, glob, hunk, style, terminal, tokeniser};
+use dyff::style::StyleOpts;
+use dyff::printer::{Event, LineCounter, Printer, TerminalPrinter};
+use dyff::json::JsonPrinter;
+use dyff::html::HtmlPrinter;
+use dyff::markers::{MarkerOpts, MarkersPrinter};
+use dyff::moved::MovedPrinter;
+use dyff::stat::StatPrinter;
+use dyff::theme::Theme;
+use dyff::color_depth::ColorDepth;
+use dyff::hyperlink::Hyperlinks;
+
 mod regexes;
+mod config;
+use regexes::byte_regex;
 use hunk::Hunk;
-use types::*;
-
-fn strip_style<'a>(string: Bytes<'a>, replace: &[u8]) -> Cow<'a, bstr::BStr> {
-    match byte_regex!(r"\x1b\[[\d;]*m".replace_all(string, replace)) {
-        Cow::Borrowed(x) => Cow::Borrowed(x.into()),
-        Cow::Owned(x) => Cow::Owned(x.into()),
-    }
-}
 
 fn shell_quote<S: AsRef<str>>(val: S) -> String {
     let mut val = val.as_ref().replace('\'', "'\\''");
@@ -42,16 +39,59 @@ enum AutoChoices {
     Always,
 }
 
+#[derive(Clone, PartialEq, Debug, clap::ValueEnum)]
+enum Output {
+    Terminal,
+    Json,
+    Html,
+    Markers,
+}
+
+#[derive(Clone, PartialEq, Debug, clap::ValueEnum)]
+enum WordMode {
+    Auto,
+    Code,
+    Chars,
+    Whole,
+}
+
+#[derive(Clone, PartialEq, Debug, clap::ValueEnum)]
+enum Engine {
+    Auto,
+    Builtin,
+    External,
+}
+
 #[derive(Debug, clap::Parser)]
-#[command(name = "diff")]
+#[command(name = "diff", args_override_self = true)]
 struct Cli {
 
+    /// read settings from FILE (default: $XDG_CONFIG_HOME/dyff/config.toml)
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// use the settings from [profile.NAME] in the config file
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
+
     #[arg(long, value_enum, default_value_t = AutoChoices::Auto)]
     color: AutoChoices,
 
+    /// colour theme, auto picks dark or light by asking the terminal for its background colour
+    #[arg(long, value_enum, default_value_t = Theme::Dark)]
+    theme: Theme,
+
+    /// rewrite colours to fit the terminal, auto guesses from $COLORTERM and $TERM
+    #[arg(long, value_enum, default_value_t = ColorDepth::Auto)]
+    color_depth: ColorDepth,
+
     #[arg(short = 'N', long = "no-line-numbers", action = clap::ArgAction::SetFalse)]
     line_numbers: bool,
 
+    /// columns for each line number (default: just enough for the largest line number in each hunk)
+    #[arg(long, value_name = "NUM")]
+    line_number_width: Option<usize>,
+
     #[arg(short, long)]
     signs: bool,
 
@@ -61,9 +101,108 @@ struct Cli {
     #[arg(long)]
     exact: bool,
 
+    /// print exactly one line for each input line, e.g. for git's interactive.diffFilter (implies --exact)
+    #[arg(long)]
+    preserve_lines: bool,
+
+    /// show lines that were moved elsewhere in the diff in their own colours
+    /// (waits for the whole diff before printing anything)
+    #[arg(long)]
+    color_moved: bool,
+
+    /// print a table of the lines added, removed and partly changed in each file after the diff
+    #[arg(long, conflicts_with = "preserve_lines")]
+    stat: bool,
+
+    /// like --stat but print the table before the diff
+    /// (waits for the whole diff before printing anything)
+    #[arg(long, conflicts_with = "preserve_lines")]
+    summary: bool,
+
+    /// syntax highlight rust, python, shell, json and yaml files, picked by their file names
+    #[arg(long)]
+    syntax: bool,
+
+    /// link file names and line numbers to the files, for terminals that support OSC 8 hyperlinks
+    #[arg(long)]
+    hyperlinks: bool,
+
+    /// url for --hyperlinks, {path} is the absolute path and {line} the line number
+    #[arg(long, value_name = "TEMPLATE", default_value = "file://{path}")]
+    hyperlink_format: String,
+
+    /// how to split lines into words: picked by file name, code (splits camelCase, digits etc), characters or whole words
+    #[arg(long, value_enum, default_value_t = WordMode::Auto)]
+    word_mode: WordMode,
+
+    /// split lines into words that match REGEX, and everything else into characters (overrides --word-mode)
+    #[arg(long, value_name = "REGEX")]
+    word_regex: Option<String>,
+
+    /// highlight only the characters that changed in short, similar words, e.g. typos and version numbers
+    #[arg(long)]
+    char_diff: bool,
+
+    /// show lines that only changed their indentation as removed and added lines,
+    /// instead of once with the change in indentation next to the line numbers
+    #[arg(long = "no-reindent", action = clap::ArgAction::SetFalse)]
+    reindent: bool,
+
+    /// show changes in the amount of whitespace, and whitespace at the end of lines, as if they matched
+    #[arg(short = 'b', long)]
+    ignore_space_change: bool,
+
+    /// show all changes in whitespace as if they matched
+    #[arg(short = 'w', long)]
+    ignore_all_space: bool,
+
+    /// show tabs, carriage returns, zero width spaces and other invisible characters as glyphs
+    /// (bidi control characters in changed lines are always shown)
+    #[arg(long)]
+    show_invisibles: bool,
+
+    /// output in two columns
+    #[arg(short = 'y', long)]
+    side_by_side: bool,
+
+    /// output at most NUM columns when using --side-by-side (default: terminal width)
+    #[arg(short = 'W', long, value_name = "NUM")]
+    width: Option<usize>,
+
     #[arg(short, long)]
     filter: Option<String>,
 
+    /// output format: terminal, one json object per line, a html document, or plain text with markers
+    #[arg(long, value_enum, default_value_t = Output::Terminal)]
+    output: Output,
+
+    /// print plain text with changed words between markers, e.g. [-old-]{+new+} (same as --output=markers)
+    #[arg(long)]
+    markers: bool,
+
+    #[command(flatten)]
+    marker_opts: MarkerOpts,
+
+    /// how to diff FILE1 and FILE2: builtin, or by running the external diff command
+    #[arg(long, value_enum, default_value_t = Engine::Auto)]
+    engine: Engine,
+
+    /// output NUM lines of unified context
+    #[arg(short = 'U', long, value_name = "NUM")]
+    unified: Option<usize>,
+
+    /// recursively compare any subdirectories found
+    #[arg(short, long)]
+    recursive: bool,
+
+    /// exclude files and directories that match PAT
+    #[arg(short = 'x', long, value_name = "PAT")]
+    exclude: Vec<String>,
+
+    /// only compare files that match PAT
+    #[arg(long, value_name = "PAT")]
+    include: Vec<String>,
+
     /// use LABEL instead of file name and timestamp (can be repeated)
     #[arg(long)]
     label: Vec<String>,
@@ -80,143 +219,84 @@ struct Cli {
     extras: Vec<String>,
 }
 
-#[derive(Debug, Clone, clap::Args)]
-pub struct StyleOpts {
-    #[arg(long, default_value_t = style::HEADER.into())]
-    header: Cow<'static, str>,
-    #[arg(long, default_value_t = style::COMMIT.into())]
-    commit: Cow<'static, str>,
-    #[arg(long, default_value_t = style::BACKGROUND.into())]
-    background: Cow<'static, str>,
-    #[arg(long, default_value_t = style::CONTEXT.into())]
-    context: Cow<'static, str>,
-    #[arg(long, default_value_t = style::LINENO.into())]
-    lineno: Cow<'static, str>,
-    #[arg(long, default_value_t = style::LINENO_DIFF.0.into())]
-    lineno_left: Cow<'static, str>,
-    #[arg(long, default_value_t = style::LINENO_DIFF.1.into())]
-    lineno_right: Cow<'static, str>,
-    #[arg(long, default_value_t = style::LINENO_BAR.into())]
-    lineno_bar: Cow<'static, str>,
-
-    #[arg(long, default_value_t = style::LINENO_OUR_BAR.into())]
-    lineno_our_bar: Cow<'static, str>,
-    #[arg(long, default_value_t = style::LINENO_THEIR_BAR.into())]
-    lineno_their_bar: Cow<'static, str>,
-    #[arg(long, default_value_t = style::LINENO_MERGE_BAR.into())]
-    lineno_merge_bar: Cow<'static, str>,
-
-    #[arg(long, default_value_t = style::FILENAME.2.into())]
-    filename: Cow<'static, str>,
-    #[arg(long, default_value_t = style::FILENAME.0.into())]
-    filename_left: Cow<'static, str>,
-    #[arg(long, default_value_t = style::FILENAME.1.into())]
-    filename_right: Cow<'static, str>,
-    #[arg(long, default_value_t = style::FILENAME_RENAME.into())]
-    filename_rename: Cow<'static, str>,
-    #[arg(long, default_value_t = style::FILENAME_HEADER.0.into())]
-    filename_header_left: Cow<'static, str>,
-    #[arg(long, default_value_t = style::FILENAME_HEADER.1.into())]
-    filename_header_right: Cow<'static, str>,
-    #[arg(long, default_value_t = style::FILENAME_SIGN.2.into())]
-    filename_sign: Cow<'static, str>,
-    #[arg(long, default_value_t = style::FILENAME_SIGN.0.into())]
-    filename_sign_left: Cow<'static, str>,
-    #[arg(long, default_value_t = style::FILENAME_SIGN.1.into())]
-    filename_sign_right: Cow<'static, str>,
-    #[arg(long, default_value_t = style::FILENAME_NON_MATCHING[0].into())]
-    filename_non_matching_left: Cow<'static, str>,
-    #[arg(long, default_value_t = style::FILENAME_NON_MATCHING[1].into())]
-    filename_non_matching_right: Cow<'static, str>,
-
-    #[arg(long, default_value_t = style::DIFF_MATCHING[0].into())]
-    diff_matching_left: Cow<'static, str>,
-    #[arg(long, default_value_t = style::DIFF_MATCHING[1].into())]
-    diff_matching_right: Cow<'static, str>,
-
-    #[arg(long, default_value_t = style::DIFF_NON_MATCHING[0].into())]
-    diff_non_matching_left: Cow<'static, str>,
-    #[arg(long, default_value_t = style::DIFF_NON_MATCHING[1].into())]
-    diff_non_matching_right: Cow<'static, str>,
-
-    #[arg(long, default_value_t = style::DIFF_INSERT[0].into())]
-    diff_insert_left: Cow<'static, str>,
-    #[arg(long, default_value_t = style::DIFF_INSERT[1].into())]
-    diff_insert_right: Cow<'static, str>,
-
-    #[arg(long, default_value_t = style::DIFF_MATCHING_INLINE.into())]
-    diff_matching_inline: Cow<'static, str>,
-    #[arg(long, default_value_t = style::DIFF_CONTEXT.into())]
-    diff_context: Cow<'static, str>,
-    #[arg(long, default_value_t = style::DIFF_TRAILING_WS.into())]
-    diff_trailing_ws: Cow<'static, str>,
+// the directory that file names in the diff are relative to
+// adds a hidden opposite for every flag, e.g. --no-signs and --line-numbers,
+// so that a flag turned on in a config file can be turned off again
+fn with_negations(command: clap::Command) -> clap::Command {
+    let flags: Vec<_> = command.get_arguments()
+        .filter(|a| matches!(a.get_action(), clap::ArgAction::SetTrue | clap::ArgAction::SetFalse))
+        .filter_map(|a| Some((a.get_id().clone(), a.get_long()?.to_owned())))
+        .collect();
+
+    flags.into_iter().fold(command, |command, (id, long)| {
+        let negation = format!("not_{id}");
+        command
+            .mut_arg(&id, |a| a.overrides_with(negation.clone()))
+            .arg(clap::Arg::new(negation).long(config::negate(&long)).action(clap::ArgAction::SetTrue).hide(true).overrides_with(id))
+    })
 }
 
-impl StyleOpts {
-    fn insert_background(&mut self) {
-        // insert the bg everywhere
-        if !self.background.is_empty() {
-            let mut replacement = "\x1b[0m".to_owned();
-            replacement += &self.background;
-            replacement += style::PAINT_RIGHT;
-            replacement += "\x1b[";
-
-            macro_rules! replace {
-                ( $name:expr ) => {
-                    let pat = "\x1b[0;";
-                    if $name.contains(pat) {
-                        $name = Cow::from($name.replace(pat, &replacement));
-                    }
-                }
-            }
+fn parse_args() -> Result<Cli> {
+    let argv: Vec<OsString> = std::env::args_os().collect();
+    let command = with_negations(Cli::command());
+    let options: Vec<_> = command.get_arguments()
+        .filter_map(|a| Some((a.get_long()?, a.get_action().takes_values())))
+        .filter(|(a, _)| !["config", "help"].contains(a))
+        .collect();
+
+    // the options given on the command line itself
+    let matches = command.clone().get_matches_from(&argv);
+    let command_line: Vec<_> = command.get_arguments()
+        .filter(|a| matches.value_source(a.get_id().as_str()) == Some(clap::parser::ValueSource::CommandLine))
+        .filter_map(|a| a.get_long())
+        .collect();
+
+    // settings are taken from (in increasing precedence) the theme, the config file, git config, the command line
+    // so parse in stages to find out the theme and profile first
+    let parse = |layers: &[&[String]]| {
+        // an option in one layer replaces it in the layers below, rather than adding to lists like --exclude
+        let mut given = command_line.clone();
+        let mut args = vec![];
+        for layer in layers.iter().rev() {
+            let key = |arg: &String| arg.trim_start_matches('-').split('=').next().unwrap_or_default().to_owned();
+            args.splice(0..0, layer.iter().filter(|a| !given.contains(&key(a).as_str())).cloned());
+            given.extend(options.iter().map(|(o, _)| *o).filter(|o| layer.iter().any(|a| key(a) == *o)));
+        }
+
+        let matches = command.clone().get_matches_from(
+            argv[..1].iter().cloned()
+            .chain(args.into_iter().map(OsString::from))
+            .chain(argv[1..].iter().cloned())
+        );
+        Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit())
+    };
 
-            replace!(self.header);
-            replace!(self.commit);
-            // replace!(self.background);
-            replace!(self.context);
-            replace!(self.lineno);
-            replace!(self.lineno_left);
-            replace!(self.lineno_right);
-            replace!(self.lineno_bar);
-            replace!(self.lineno_our_bar);
-            replace!(self.lineno_their_bar);
-            replace!(self.lineno_merge_bar);
-            replace!(self.filename);
-            replace!(self.filename_left);
-            replace!(self.filename_right);
-            replace!(self.filename_rename);
-            replace!(self.filename_header_left);
-            replace!(self.filename_header_right);
-            replace!(self.filename_sign);
-            replace!(self.filename_sign_left);
-            replace!(self.filename_sign_right);
-            replace!(self.filename_non_matching_left);
-            replace!(self.filename_non_matching_right);
-            replace!(self.diff_matching_left);
-            replace!(self.diff_matching_right);
-            replace!(self.diff_non_matching_left);
-            replace!(self.diff_non_matching_right);
-            replace!(self.diff_insert_left);
-            replace!(self.diff_insert_right);
-            replace!(self.diff_matching_inline);
-            replace!(self.diff_context);
-            replace!(self.diff_trailing_ws);
+    let git_args = config::git_config(&options)?;
+    let mut args = parse(&[&git_args]);
 
+    let mut config_args = vec![];
+    if let Some(path) = args.config.clone().or_else(config::default_path) {
+        let options: Vec<_> = options.iter().map(|(o, _)| *o).filter(|o| *o != "profile").collect();
+        config_args = config::load(&path, args.config.is_none(), args.profile.as_deref(), &options)?;
+        if !config_args.is_empty() {
+            args = parse(&[&config_args, &git_args]);
         }
+    } else if let Some(profile) = &args.profile {
+        anyhow::bail!("no such profile {profile}: there is no config file");
     }
 
-    fn print_background<T: std::io::Write>(&self, stdout: &mut BufWriter<T>) -> Result<()> {
-        if !self.background.is_empty() {
-            stdout.write_all(self.background.as_bytes())?;
-            stdout.write_all(style::PAINT_RIGHT.as_bytes())?;
-        }
-        Ok(())
+    // only ask the terminal when the output goes to it in colour,
+    // not when piped or run as git's interactive.diffFilter, where the reply could end up on screen
+    let query = args.color != AutoChoices::Never && std::io::stdout().is_terminal();
+    let theme_args = args.theme.args(query);
+    if !theme_args.is_empty() {
+        args = parse(&[&theme_args, &config_args, &git_args]);
     }
+    Ok(args)
 }
 
-
 fn _main() -> Result<ExitCode> {
-    let mut args = Cli::parse();
+    let mut args = parse_args()?;
 
     {
         fn not_flag<S: AsRef<str>>(x: S) -> bool {
@@ -242,7 +322,17 @@ fn _main() -> Result<ExitCode> {
 
     let stdout = std::io::stdout().lock();
     let is_tty = stdout.is_terminal();
-    if !is_tty {
+    if args.markers {
+        args.output = Output::Markers;
+    }
+    // the line count is only checked for the terminal output
+    if args.preserve_lines && args.output != Output::Terminal {
+        anyhow::bail!("--preserve-lines only works with --output=terminal");
+    }
+    if args.output != Output::Terminal {
+        // structured output needs the diff to be parsed
+        args.color = AutoChoices::Always;
+    } else if !is_tty {
         if args.color == AutoChoices::Auto {
             args.color = AutoChoices::Never;
         }
@@ -251,11 +341,30 @@ fn _main() -> Result<ExitCode> {
         }
     }
 
+    if args.preserve_lines {
+        args.exact = true;
+        args.side_by_side = false;
+    }
+
+    args.style.set_color_depth(args.color_depth);
     args.style.insert_background();
     let style = style::Style{
         line_numbers: args.line_numbers,
         signs: args.signs,
-        inline: args.inline != AutoChoices::Never && !args.exact,
+        inline: args.inline != AutoChoices::Never && !args.exact && !args.side_by_side,
+        side_by_side: args.side_by_side.then(|| args.width.or_else(terminal::width).unwrap_or(80)),
+        preserve_lines: args.preserve_lines,
+        lineno_width: args.line_number_width.unwrap_or(style::LINENO_WIDTH),
+        char_diff: args.char_diff,
+        reindent: args.reindent,
+        ignore_space: if args.ignore_all_space {
+            Some(style::IgnoreSpace::All)
+        } else if args.ignore_space_change {
+            Some(style::IgnoreSpace::Change)
+        } else {
+            None
+        },
+        show_invisibles: args.show_invisibles,
 
         diff_matching: [(*args.style.diff_matching_left).into(), (*args.style.diff_matching_right).into()],
         diff_matching_inline: (*args.style.diff_matching_inline).into(),
@@ -263,6 +372,118 @@ fn _main() -> Result<ExitCode> {
         ..style::Style::default()
     };
 
+    // pull out the context length so both engines can use it
+    for x in std::mem::take(&mut args.extras) {
+        if let Some(context) = x.strip_prefix("-U").or_else(|| x.strip_prefix("--unified="))
+        && let Ok(context) = context.parse() {
+            args.unified = Some(context);
+        } else {
+            args.extras.push(x);
+        }
+    }
+
+    let words = match (&args.word_regex, &args.word_mode) {
+        (Some(regex), _) => Some(tokeniser::Words::Regex(regex::bytes::Regex::new(regex).context("invalid --word-regex")?)),
+        (None, WordMode::Auto) => None,
+        (None, WordMode::Code) => Some(tokeniser::Words::Code),
+        (None, WordMode::Chars) => Some(tokeniser::Words::Chars),
+        (None, WordMode::Whole) => Some(tokeniser::Words::Whole),
+    };
+
+    let lines_written = Rc::new(Cell::new(0));
+    let stdout = LineCounter::new(stdout, lines_written.clone());
+    let mut printer: Box<dyn Printer> = match args.output {
+        Output::Terminal => {
+            let mut printer = TerminalPrinter::new(stdout, args.color != AutoChoices::Never, style, &args.style);
+            printer.syntax = args.syntax && printer.color;
+            printer.lineno_width = args.line_number_width;
+            if args.hyperlinks && printer.color {
+                printer.hyperlinks = Some(Hyperlinks::new(args.hyperlink_format.clone(), std::env::current_dir()?));
+            }
+            // moved lines would break up the columns or the order of the lines
+            if args.color_moved && !args.side_by_side && !args.preserve_lines {
+                Box::new(MovedPrinter::new(printer))
+            } else {
+                Box::new(printer)
+            }
+        },
+        Output::Json => Box::new(JsonPrinter::new(stdout)),
+        Output::Html => {
+            let mut printer = HtmlPrinter::new(stdout);
+            printer.show_invisibles = args.show_invisibles;
+            Box::new(printer)
+        },
+        Output::Markers => {
+            let mut printer = MarkersPrinter::new(stdout, &args.marker_opts);
+            printer.show_invisibles = args.show_invisibles;
+            Box::new(printer)
+        },
+    };
+    // the table would add lines that are not in the input
+    if args.stat || args.summary {
+        printer = Box::new(StatPrinter::new(printer, args.summary));
+    }
+
+    let engine = match args.engine {
+        // the builtin engine only does unified diffs, so fall back for anything else
+        Engine::Auto if args.extras.iter().all(|x| x == "-u") => Engine::Builtin,
+        Engine::Auto => Engine::External,
+        engine => engine,
+    };
+
+    if engine == Engine::Builtin && let Some((file1, file2)) = args.file1.as_ref().zip(args.file2.as_ref()) {
+        if let Some(x) = args.extras.iter().find(|x| *x != "-u") {
+            anyhow::bail!("unsupported option for the builtin engine: {x}");
+        }
+
+        let mut builtin = builtin::Builtin{
+            printer: &mut *printer,
+            tokeniser: tokeniser::Tokeniser::with_words(words),
+            filter: args.filter.as_deref(),
+            context: args.unified.unwrap_or(3),
+            recursive: args.recursive,
+            globs: glob::GlobFilter{include: args.include.clone(), exclude: args.exclude.clone()},
+            color: args.color != AutoChoices::Never,
+            ignore_space: style.ignore_space,
+            trouble: false,
+        };
+        let differs = builtin.diff([file1, file2], &args.label);
+        let trouble = builtin.trouble;
+        let mut tokeniser = builtin.tokeniser;
+        printer.finish(&mut tokeniser)?;
+        // like diff, 2 is for trouble so that it can't be mistaken for files that differ
+        return Ok(match differs {
+            Ok(_) if trouble => ExitCode::from(2),
+            Ok(true) => ExitCode::FAILURE,
+            Ok(false) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("dyff: {e:#}");
+                ExitCode::from(2)
+            },
+        })
+    }
+
+    // like -r and -x, this is ignored when filtering a diff, as it may well come from the config file
+    if args.file1.is_some() && args.file2.is_some() && !args.include.is_empty() {
+        anyhow::bail!("--include is not supported by the external engine");
+    }
+    if args.recursive {
+        args.extras.insert(0, "-r".into());
+    }
+    // diff leaves out hunks that only change whitespace, like it did when these were passed through
+    if args.ignore_all_space {
+        args.extras.insert(0, "--ignore-all-space".into());
+    } else if args.ignore_space_change {
+        args.extras.insert(0, "--ignore-space-change".into());
+    }
+    for pat in args.exclude.iter() {
+        args.extras.insert(0, format!("--exclude={pat}"));
+    }
+    if let Some(context) = args.unified {
+        args.extras.retain(|x| x != "-u");
+        args.extras.insert(0, format!("-U{context}"));
+    }
+
     let command;
     let mut diff_proc = if let Some((file1, file2)) = args.file1.zip(args.file2) {
         let mut diff_args;
@@ -310,17 +531,22 @@ fn _main() -> Result<ExitCode> {
     let mut stdin = std::io::stdin().lock();
 
     let mut hunk: Option<Hunk> = None;
-    let mut tokeniser = tokeniser::Tokeniser::new();
+    let mut tokeniser = tokeniser::Tokeniser::with_words(words);
     let mut line_numbers = [0, 0];
     let mut unified = false;
+    // context diffs give each side in its own section
+    // this is the current section and whether each section has been given
+    let mut context_section: Option<(usize, [bool; 2])> = None;
+    // context lines from the first section, in case the second is left out
+    let mut context_lines: Vec<bstr::BString> = vec![];
+    let mut context_filename = false;
     let mut merge_markers: Option<hunk::MergeMarkers> = None;
     let mut filename: Option<bstr::BString> = None;
-    let mut stdout = BufWriter::new(stdout);
-
-    let mut diff_trailing_ws_pat = regex::escape(&args.style.diff_trailing_ws).into_bytes();
-    diff_trailing_ws_pat.extend(b"$0");
+    // the a/ before the old name
+    let mut filename_prefix = bstr::BString::default();
 
     let mut buf = bstr::BString::default();
+    let mut lines_read = 0;
     let mut diff = false;
     let mut side = 0;
     loop {
@@ -331,30 +557,23 @@ fn _main() -> Result<ExitCode> {
             x => x?,
         };
         diff = true;
+        lines_read += 1;
 
         if args.color == AutoChoices::Never {
-            stdout.write_all(&buf)?;
+            printer.print(&mut tokeniser, Event::Raw{line: &buf})?;
             continue
         }
 
-        let stripped = strip_style(buf.as_ref(), b"");
+        let stripped = style::strip_style(buf.as_ref(), b"");
 
         if let Some(captures) = byte_regex!(r"^((?<header>@@ -(?<line_minus>\d+)(,\d+)? \+(?<line_plus>\d+)(,\d+)? @@)\s*)(?<context>.*)".captures(&stripped)) {
             unified = true;
             merge_markers = None;
+            context_section = None;
             if let Some(mut hunk) = hunk {
-                hunk.print(&mut stdout, &mut tokeniser, line_numbers, merge_markers.as_ref(), style, &args.style)?;
-            }
-            args.style.print_background(&mut stdout)?;
-            stdout.write_all(args.style.header.as_bytes())?;
-            stdout.write_all(&captures["header"])?;
-            if !captures["context"].is_empty() {
-                stdout.write_all(b" ")?;
-                stdout.write_all(args.style.context.as_bytes())?;
-                stdout.write_all(&captures["context"])?;
+                printer.print(&mut tokeniser, Event::Hunk{hunk: &mut hunk, line_numbers, merge_markers: merge_markers.as_ref()})?;
             }
-            stdout.write_all(style::RESET)?;
-            stdout.write_all(b"\n")?;
+            printer.print(&mut tokeniser, Event::HunkHeader{header: &captures["header"], context: &captures["context"]})?;
             hunk = Some(Hunk::new());
             line_numbers = [
                 std::str::from_utf8(&captures["line_minus"])?.parse()?,
@@ -366,17 +585,11 @@ fn _main() -> Result<ExitCode> {
         if let Some(captures) = byte_regex!(r"^((?<header>@@@ -(?<our_line_minus>\d+)(,\d+)? -(?<their_line_minus>\d+)(,\d+)? \+(?<line_plus>\d+)(,\d+)? @@@)\s*)(?<context>.*)".captures(&stripped)) {
             unified = true;
             merge_markers = Some(HashMap::new());
+            context_section = None;
             if let Some(mut hunk) = hunk {
-                hunk.print(&mut stdout, &mut tokeniser, line_numbers, merge_markers.as_ref(), style, &args.style)?;
+                printer.print(&mut tokeniser, Event::Hunk{hunk: &mut hunk, line_numbers, merge_markers: merge_markers.as_ref()})?;
             }
-            args.style.print_background(&mut stdout)?;
-            stdout.write_all(args.style.header.as_bytes())?;
-            stdout.write_all(&captures["header"])?;
-            stdout.write_all(b" ")?;
-            stdout.write_all(args.style.context.as_bytes())?;
-            stdout.write_all(&captures["context"])?;
-            stdout.write_all(style::RESET)?;
-            stdout.write_all(b"\n")?;
+            printer.print(&mut tokeniser, Event::MergeHunkHeader{header: &captures["header"], context: &captures["context"]})?;
             hunk = Some(Hunk::new());
             line_numbers = [
                 std::str::from_utf8(&captures["our_line_minus"])?.parse()?,
@@ -388,13 +601,11 @@ fn _main() -> Result<ExitCode> {
         if let Some(captures) = byte_regex!(r"^(?<line_minus>\d+)(,\d+)?[acd](?<line_plus>\d+)(,\d+)?".captures(&stripped)) {
             unified = false;
             merge_markers = None;
+            context_section = None;
             if let Some(mut hunk) = hunk {
-                hunk.print(&mut stdout, &mut tokeniser, line_numbers, merge_markers.as_ref(), style, &args.style)?;
+                printer.print(&mut tokeniser, Event::Hunk{hunk: &mut hunk, line_numbers, merge_markers: merge_markers.as_ref()})?;
             }
-            args.style.print_background(&mut stdout)?;
-            stdout.write_all(args.style.header.as_bytes())?;
-            stdout.write_all(&buf)?;
-            stdout.write_all(style::RESET)?;
+            printer.print(&mut tokeniser, Event::NormalHunkHeader{line: &buf})?;
             hunk = Some(Hunk::new());
             line_numbers = [
                 std::str::from_utf8(&captures["line_minus"])?.parse()?,
@@ -403,6 +614,19 @@ fn _main() -> Result<ExitCode> {
             continue
         }
 
+        if byte_regex!(r"^\*{15}\n".is_match(&stripped)) {
+            if args.preserve_lines {
+                anyhow::bail!("--preserve-lines does not support context diffs");
+            }
+            unified = false;
+            merge_markers = None;
+            context_section = Some((0, [false, false]));
+            if let Some(mut hunk) = hunk {
+                printer.print(&mut tokeniser, Event::Hunk{hunk: &mut hunk, line_numbers, merge_markers: merge_markers.as_ref()})?;
+            }
+            hunk = Some(Hunk::new());
+            continue
+        }
 
         if let Some(captures) =
             byte_regex!("^(?<header>diff( -r| --recursive)?) (?<filename1>[^\"\\s-][^\"\\s]+|\"(\\\\.|.)*\") (?<filename2>[^\"\\s]+|\"(\\\\.|.)*\")(?<trailer>.*)".captures(&stripped))
@@ -411,50 +635,57 @@ fn _main() -> Result<ExitCode> {
             )
         {
             if let Some(mut hunk) = hunk {
-                hunk.print(&mut stdout, &mut tokeniser, line_numbers, merge_markers.as_ref(), style, &args.style)?;
+                printer.print(&mut tokeniser, Event::Hunk{hunk: &mut hunk, line_numbers, merge_markers: merge_markers.as_ref()})?;
             }
-            args.style.print_background(&mut stdout)?;
-            stdout.write_all(style::DIFF_HEADER.as_bytes())?;
-            stdout.write_all(&captures["header"])?;
-            stdout.write_all(b" ")?;
-            stdout.write_all(style::RESET)?;
-            stdout.write_all(args.style.filename_header_left.as_bytes())?;
-            stdout.write_all(&captures["filename1"])?;
-            stdout.write_all(b" ")?;
-            stdout.write_all(args.style.filename_header_right.as_bytes())?;
-            stdout.write_all(&captures["filename2"])?;
-            stdout.write_all(style::RESET)?;
-            stdout.write_all(&captures["trailer"])?;
-            stdout.write_all(b"\n")?;
+            let event = Event::DiffHeader{
+                header: &captures["header"],
+                filename1: &captures["filename1"],
+                filename2: &captures["filename2"],
+                trailer: &captures["trailer"],
+            };
+            // the words to split the hunks into are picked by file name
+            tokeniser.set_filename(event.filename().unwrap_or_default());
+            printer.print(&mut tokeniser, event)?;
             hunk = Some(Hunk::new());
+            context_section = None;
             continue
         }
 
+        if context_section.is_some() && stripped.starts_with(b"*** ") && !byte_regex!(r"^\*\*\* \d+(,\d+)? \*\*\*\*\n".is_match(&stripped)) {
+            // the next file in a context diff
+            if let Some(mut hunk) = hunk.take() {
+                printer.print(&mut tokeniser, Event::Hunk{hunk: &mut hunk, line_numbers, merge_markers: merge_markers.as_ref()})?;
+            }
+            context_section = None;
+        }
+
         if hunk.is_none() {
-            if let Some(captures) = byte_regex!(r"^(?<sign>---|\+\+\+) ([ab]/)?(?<filename>[^\t]*)(?<trailer>\t.*)?".captures(&stripped)) {
-                if &captures["sign"] == b"---" {
+            if let Some(captures) = byte_regex!(r"^(?<sign>\*\*\*|---|\+\+\+) (?<prefix>[ab]/)?(?<filename>[^\t]*)(?<trailer>\t.*)?".captures(&stripped)) {
+                let sign = &captures["sign"];
+                let prefix = captures.name("prefix").map_or(b"".as_slice(), |p| p.as_bytes());
+                // context diffs use *** and --- instead of --- and +++
+                if sign == b"***" || (sign == b"---" && !context_filename) {
+                    context_filename = sign == b"***";
                     filename = Some(captures["filename"].to_owned().into());
+                    filename_prefix = prefix.into();
                 } else {
-                    Hunk::print_filename(
-                        &mut stdout,
-                        &mut tokeniser,
-                        filename.as_ref().map(|f| f.as_ref()),
-                        Some(bstr::BStr::new(&captures["filename"])),
-                        (&args.style.filename_sign_left, &args.style.filename_sign_right, &args.style.filename_sign),
-                        style,
-                        &args.style,
-                    )?;
+                    context_filename = false;
+                    let event = Event::Filename{
+                        left: filename.as_ref().map(|f| f.as_ref()),
+                        right: Some(bstr::BStr::new(&captures["filename"])),
+                        rename: false,
+                        prefixes: [&filename_prefix, prefix],
+                    };
+                    tokeniser.set_filename(event.filename().unwrap_or_default());
+                    printer.print(&mut tokeniser, event)?;
                 }
                 continue
             }
 
-            args.style.print_background(&mut stdout)?;
             if byte_regex!(r"^commit [0-9a-f]+".is_match(&stripped)) {
-                stdout.write_all(args.style.commit.as_bytes())?;
-                stdout.write_all(&strip_style(buf.as_ref(), format!("$0{}", args.style.commit).as_bytes()))?;
-                stdout.write_all(style::RESET)?;
+                printer.print(&mut tokeniser, Event::Commit{line: buf.as_ref()})?;
             } else {
-                stdout.write_all(&buf)?;
+                printer.print(&mut tokeniser, Event::Text{line: &buf})?;
             }
             continue
         }
@@ -478,21 +709,53 @@ fn _main() -> Result<ExitCode> {
             continue
         }
 
-        if args.exact && stripped.starts_with(b" ") {
-            h.print(&mut stdout, &mut tokeniser, line_numbers, merge_markers.as_ref(), style, &args.style)?;
-            args.style.print_background(&mut stdout)?;
-            if style.line_numbers {
-                stdout.write_all(style::format_lineno(
-                        line_numbers,
-                        Some(&args.style.lineno), Some(&args.style.lineno),
-                        None,
-                ).as_ref())?;
+        if let Some((section, given)) = &mut context_section {
+            if let Some(captures) = byte_regex!(r"^\*\*\* (?<line>\d+)(,\d+)? \*\*\*\*".captures(&stripped)) {
+                *section = 0;
+                context_lines.clear();
+                line_numbers[0] = std::str::from_utf8(&captures["line"])?.parse()?;
+                printer.print(&mut tokeniser, Event::NormalHunkHeader{line: &buf})?;
+                continue
+            }
+
+            if let Some(captures) = byte_regex!(r"^--- (?<line>\d+)(,\d+)? ----".captures(&stripped)) {
+                *section = 1;
+                line_numbers[1] = std::str::from_utf8(&captures["line"])?.parse()?;
+                if given[0] {
+                    // until we know otherwise, the second section has been left out as it is only context
+                    h.right = context_lines.clone();
+                }
+                continue
             }
-            if style.signs {
-                stdout.write_all(style::SIGN[2])?;
+
+            if let Some(captures) = byte_regex!(r"^(?<sign>[ !+-]) (?<line>.*\n)".captures(&stripped)) {
+                let line: bstr::BString = captures["line"].to_owned().into();
+                let context = &captures["sign"] == b" ";
+                side = *section;
+
+                if side == 0 {
+                    if context {
+                        context_lines.push(line.clone());
+                    }
+                } else if !given[1] {
+                    h.right.clear();
+                }
+                if side == 1 && context && !given[0] {
+                    // the first section was left out as it is only context
+                    h.left.push(line.clone());
+                }
+
+                given[side] = true;
+                h.get_mut(side).push(line);
+                continue
             }
-            stdout.write_all(args.style.diff_context.as_bytes())?;
-            stdout.write_all(&byte_regex!(r"\s+\n".replace_all(&stripped[1..], &diff_trailing_ws_pat)))?;
+        }
+
+        if args.exact && stripped.starts_with(b" ") {
+            printer.print(&mut tokeniser, Event::Hunk{hunk: h, line_numbers, merge_markers: merge_markers.as_ref()})?;
+            line_numbers[0] += h.left.len();
+            line_numbers[1] += h.right.len();
+            printer.print(&mut tokeniser, Event::Context{line_numbers, line: &stripped[1..]})?;
 
             hunk = Some(Hunk::new());
             line_numbers[0] += 1;
@@ -506,21 +769,21 @@ fn _main() -> Result<ExitCode> {
             if &captures["sign"] == b"from" {
                 filename = Some(captures["filename"].to_owned().into());
             } else {
-                Hunk::print_filename(
-                    &mut stdout,
-                    &mut tokeniser,
-                    filename.as_ref().map(|f| f.as_ref()),
-                    Some(bstr::BStr::new(&captures["filename"])),
-                    ("rename from\t", "rename to\t", "rename from/to\t"),
-                    style,
-                    &args.style,
-                )?;
+                printer.print(&mut tokeniser, Event::Filename{
+                    left: filename.as_ref().map(|f| f.as_ref()),
+                    right: Some(bstr::BStr::new(&captures["filename"])),
+                    rename: true,
+                    prefixes: [b"", b""],
+                })?;
             }
             continue
         }
 
         if *stripped == b"\\ No newline at end of file\n" || *stripped == b"\\ No newline at end of file" {
-            if let Some(last_line) = h.get_mut(side).last_mut()
+            if h.is_empty() {
+                // the line was context and has already been printed
+                printer.print(&mut tokeniser, Event::Text{line: &buf})?;
+            } else if let Some(last_line) = h.get_mut(side).last_mut()
             && last_line.ends_with(b"\n") {
                 last_line.pop();
             }
@@ -541,6 +804,7 @@ fn _main() -> Result<ExitCode> {
 
         if !unified {
             if *stripped == b"---\n" {
+                h.separator = true;
                 continue
             }
 
@@ -552,27 +816,30 @@ fn _main() -> Result<ExitCode> {
         }
 
         if &buf == b"\n" {
-            h.print(&mut stdout, &mut tokeniser, line_numbers, merge_markers.as_ref(), style, &args.style)?;
+            printer.print(&mut tokeniser, Event::Hunk{hunk: h, line_numbers, merge_markers: merge_markers.as_ref()})?;
             hunk = None;
-            args.style.print_background(&mut stdout)?;
-            stdout.write_all(b"\n")?;
+            printer.print(&mut tokeniser, Event::Text{line: b"\n"})?;
             continue
         }
 
-        h.print(&mut stdout, &mut tokeniser, line_numbers, merge_markers.as_ref(), style, &args.style)?;
+        printer.print(&mut tokeniser, Event::Hunk{hunk: h, line_numbers, merge_markers: merge_markers.as_ref()})?;
         if byte_regex!("^index ".is_match(&stripped)) {
-            args.style.print_background(&mut stdout)?;
-            stdout.write_all(&strip_style(buf.as_ref(), format!("$0{}", style::DIFF_HEADER).as_bytes()))?;
+            printer.print(&mut tokeniser, Event::Index{line: buf.as_ref()})?;
             hunk = None;
             continue
         }
 
         hunk = Some(Hunk::new());
-        stdout.write_all(&stripped)?;
+        printer.print(&mut tokeniser, Event::Raw{line: &stripped})?;
     }
 
     if let Some(mut hunk) = hunk {
-        hunk.print(&mut stdout, &mut tokeniser, line_numbers, merge_markers.as_ref(), style, &args.style)?;
+        printer.print(&mut tokeniser, Event::Hunk{hunk: &mut hunk, line_numbers, merge_markers: merge_markers.as_ref()})?;
+    }
+    printer.finish(&mut tokeniser)?;
+
+    if args.preserve_lines && lines_written.get() != lines_read {
+        anyhow::bail!("--preserve-lines: read {lines_read} lines but printed {}, please report this as a bug", lines_written.get());
     }
 
     if let Some(mut diff_proc) = diff_proc && let Some(code) = diff_proc.try_wait()?.and_then(|x| x.code()) {
@@ -0,0 +1,140 @@
+use std::io::{BufWriter, Write};
+use anyhow::Result;
+use super::printer::{Event, Printer, trim_newline};
+use super::tokeniser::Tokeniser;
+use super::invisibles::{self, Show};
+use super::DiffBlock;
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct MarkerOpts {
+    /// text before deleted words with --markers
+    #[arg(long, value_name = "TEXT", default_value = "[-")]
+    pub marker_delete_start: String,
+    /// text after deleted words with --markers
+    #[arg(long, value_name = "TEXT", default_value = "-]")]
+    pub marker_delete_end: String,
+    /// text before added words with --markers
+    #[arg(long, value_name = "TEXT", default_value = "{+")]
+    pub marker_insert_start: String,
+    /// text after added words with --markers
+    #[arg(long, value_name = "TEXT", default_value = "+}")]
+    pub marker_insert_end: String,
+}
+
+// prints plain text with the changed words between markers, like git diff --word-diff=plain
+// unchanged lines keep their leading space, changed lines are printed once with both sides merged
+pub struct MarkersPrinter<'a, T: Write> {
+    pub stdout: BufWriter<T>,
+    pub opts: &'a MarkerOpts,
+    pub show_invisibles: bool,
+}
+
+impl<'a, T: Write> MarkersPrinter<'a, T> {
+    pub fn new(stdout: T, opts: &'a MarkerOpts) -> Self {
+        Self{
+            stdout: BufWriter::new(stdout),
+            opts,
+            show_invisibles: false,
+        }
+    }
+
+    fn print_line(&mut self, prefix: &[u8], line: &[u8]) -> Result<()> {
+        let line = super::style::strip_style(line.into(), b"");
+        self.stdout.write_all(prefix)?;
+        self.stdout.write_all(trim_newline(&line))?;
+        self.stdout.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn print_block(&mut self, block: &DiffBlock) -> Result<()> {
+        if block.parts.iter().all(|p| p.matches) {
+            let show = Show::new(self.show_invisibles, false);
+            for part in block.parts.iter() {
+                for line in part.left.text.split_inclusive(|&c| c == b'\n') {
+                    self.print_line(b" ", &invisibles::replace_plain(line, show))?;
+                }
+            }
+            return Ok(())
+        }
+
+        let show = Show::new(self.show_invisibles, true);
+        let markers = [
+            (&self.opts.marker_delete_start, &self.opts.marker_delete_end),
+            (&self.opts.marker_insert_start, &self.opts.marker_insert_end),
+        ];
+        let mut output = vec![];
+        for part in block.parts.iter() {
+            if part.matches {
+                output.extend_from_slice(&invisibles::replace_plain(&part.left.text, show));
+                continue
+            }
+
+            for (i, (start, end)) in markers.iter().enumerate() {
+                // markers do not span lines
+                for line in part.get(i).text.split_inclusive(|&c| c == b'\n') {
+                    let text = trim_newline(line);
+                    if !text.is_empty() {
+                        output.extend_from_slice(start.as_bytes());
+                        output.extend_from_slice(&invisibles::replace_plain(text, show));
+                        output.extend_from_slice(end.as_bytes());
+                    }
+                    if text.len() < line.len() {
+                        output.push(b'\n');
+                    }
+                }
+            }
+        }
+
+        if !output.is_empty() && !output.ends_with(b"\n") {
+            // no newline at end of file
+            output.push(b'\n');
+        }
+        self.stdout.write_all(&output)?;
+        Ok(())
+    }
+}
+
+impl<T: Write> Printer for MarkersPrinter<'_, T> {
+    fn print(&mut self, tokeniser: &mut Tokeniser, event: Event) -> Result<()> {
+        match event {
+            Event::DiffHeader{header, filename1, filename2, trailer} => {
+                self.stdout.write_all(&[header, b" ", filename1, b" ", filename2, trailer, b"\n"].concat())?;
+            },
+            Event::Filename{left, right, rename, prefixes} => {
+                // the names are printed as they were in the diff, with git's a/ and b/
+                let (prefix_left, prefix_right) = if rename { ("rename from ", "rename to ") } else { ("--- ", "+++ ") };
+                if let Some(left) = left {
+                    self.print_line(&[prefix_left.as_bytes(), prefixes[0]].concat(), left)?;
+                }
+                if let Some(right) = right {
+                    self.print_line(&[prefix_right.as_bytes(), prefixes[1]].concat(), right)?;
+                }
+            },
+            Event::HunkHeader{header, context} | Event::MergeHunkHeader{header, context} => {
+                if context.is_empty() {
+                    self.print_line(b"", header)?;
+                } else {
+                    self.print_line(&[header, b" "].concat(), context)?;
+                }
+            },
+            Event::NormalHunkHeader{line} => self.print_line(b"", line)?,
+            Event::Hunk{hunk, line_numbers, merge_markers: _} => {
+                for block in super::diff_lines_with_words(&hunk.left, &hunk.right, line_numbers, tokeniser.current_words().clone()) {
+                    self.print_block(&block)?;
+                }
+            },
+            Event::Context{line_numbers: _, line} => {
+                self.print_line(b" ", &invisibles::replace_plain(line, Show::new(self.show_invisibles, false)))?;
+            },
+            Event::Commit{line} | Event::Index{line} => self.print_line(b"", line)?,
+            Event::OnlyIn{side: _, message} | Event::Message{message} => self.print_line(b"", message)?,
+            Event::Text{line} | Event::Raw{line} => self.print_line(b"", line)?,
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, _tokeniser: &mut Tokeniser) -> Result<()> {
+        self.stdout.flush()?;
+        Ok(())
+    }
+}
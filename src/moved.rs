@@ -0,0 +1,229 @@
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::ops::Range;
+use anyhow::Result;
+use bstr::{BString, ByteSlice};
+use super::file_differ::{FileDiffer, Opcode};
+use super::hunk::Hunk;
+use super::printer::{Buffered, Event, Printer, TerminalPrinter};
+use super::tokeniser::Tokeniser;
+use super::types::*;
+
+// how much a deletion and an addition must have in common to count as a move
+const MIN_LINES: usize = 2;
+// like git, so that moving a few braces around doesn't count
+const MIN_ALNUM: usize = 20;
+
+// a run of lines that are only on one side of a hunk
+struct Run {
+    event: usize,
+    op: usize,
+    side: usize,
+    lines: Range<usize>,
+}
+
+// lines that were moved to or from somewhere else in the diff
+struct Move {
+    side: usize,
+    lines: Range<usize>,
+    // the other copy of the lines
+    other: Line,
+    other_lineno: usize,
+}
+
+// lines are compared ignoring indentation, and lines without any words are ignored
+fn key(line: &[u8]) -> Option<&[u8]> {
+    let line = line.trim();
+    line.iter().any(|c| c.is_ascii_alphanumeric()).then_some(line)
+}
+
+// whether two lines are the same or have at least half of their words in common
+fn similar(a: &[u8], b: &[u8]) -> bool {
+    if a.trim() == b.trim() {
+        return true
+    }
+    let words = |line: &[u8]| -> HashSet<Vec<u8>> {
+        line.split(|c| !c.is_ascii_alphanumeric()).filter(|w| !w.is_empty()).map(|w| w.to_vec()).collect()
+    };
+    let (a, b) = (words(a), words(b));
+    let common = a.intersection(&b).count();
+    common > 0 && common * 2 >= a.len().min(b.len())
+}
+
+fn find_moves(events: &[Buffered], ops: &[Option<Vec<Opcode>>]) -> HashMap<usize, Vec<Move>> {
+    let mut runs = vec![];
+    for (event, ops) in ops.iter().enumerate() {
+        for (op, (matches, left, right)) in ops.iter().flatten().enumerate() {
+            for (side, lines) in [left, right].into_iter().enumerate() {
+                if !matches && !lines.is_empty() {
+                    runs.push(Run{event, op, side, lines: lines.clone()});
+                }
+            }
+        }
+    }
+
+    let run_lines = |run: &Run| {
+        let (hunk, _) = events[run.event].hunk().unwrap();
+        &hunk.get(run.side)[run.lines.clone()]
+    };
+    let run_keys = |run: &Run| run_lines(run).iter().filter_map(|l| key(l)).collect::<HashSet<_>>();
+
+    // which additions each line appears in
+    let mut added: HashMap<&[u8], Vec<usize>> = HashMap::new();
+    for (i, run) in runs.iter().enumerate().filter(|(_, r)| r.side == 1) {
+        for key in run_keys(run) {
+            added.entry(key).or_default().push(i);
+        }
+    }
+
+    // pair up deletions and additions with enough lines in common
+    let mut candidates = vec![];
+    for (i, run) in runs.iter().enumerate().filter(|(_, r)| r.side == 0) {
+        // the number of lines and alphanumerics in common with each addition
+        let mut counts: HashMap<usize, (usize, usize)> = HashMap::new();
+        for key in run_keys(run) {
+            let alnum = key.iter().filter(|c| c.is_ascii_alphanumeric()).count();
+            for &j in added.get(key).into_iter().flatten() {
+                let count = counts.entry(j).or_default();
+                count.0 += 1;
+                count.1 += alnum;
+            }
+        }
+        for (j, (lines, alnum)) in counts {
+            // a deletion and addition in the same place is just a change
+            if lines >= MIN_LINES && alnum >= MIN_ALNUM && (runs[j].event, runs[j].op) != (run.event, run.op) {
+                candidates.push((alnum, i, j));
+            }
+        }
+    }
+    // best matches first
+    candidates.sort_by_key(|&(alnum, i, j)| (std::cmp::Reverse(alnum), i, j));
+
+    let mut moves: HashMap<usize, Vec<Move>> = HashMap::new();
+    let mut used = vec![false; runs.len()];
+    for (_, i, j) in candidates {
+        if used[i] || used[j] {
+            continue
+        }
+        used[i] = true;
+        used[j] = true;
+
+        // only the part from the first to the last line in common has moved
+        let pair = [&runs[i], &runs[j]];
+        let keys = pair.map(run_keys);
+        let mut spans = [0, 1].map(|side| {
+            let run = pair[side];
+            let lines = run_lines(run);
+            let common = |l: &&BString| key(l).is_some_and(|k| keys[1-side].contains(k));
+            let first = lines.iter().position(|l| common(&l)).unwrap();
+            let last = lines.iter().rposition(|l| common(&l)).unwrap();
+            run.lines.start + first .. run.lines.start + last + 1
+        });
+
+        // also take lines on either side that were edited as they were moved
+        let lines = pair.map(|run| events[run.event].hunk().unwrap().0.get(run.side));
+        while spans.iter().zip(pair).all(|(span, run)| span.start > run.lines.start)
+        && similar(&lines[0][spans[0].start - 1], &lines[1][spans[1].start - 1]) {
+            spans[0].start -= 1;
+            spans[1].start -= 1;
+        }
+        while spans.iter().zip(pair).all(|(span, run)| span.end < run.lines.end)
+        && similar(&lines[0][spans[0].end], &lines[1][spans[1].end]) {
+            spans[0].end += 1;
+            spans[1].end += 1;
+        }
+
+        for side in 0..=1 {
+            let (other, other_line_numbers) = events[pair[1-side].event].hunk().unwrap();
+            moves.entry(pair[side].event).or_default().push(Move{
+                side,
+                lines: spans[side].clone(),
+                other: other.get(1-side)[spans[1-side].clone()].to_vec(),
+                other_lineno: other_line_numbers[1-side] + spans[1-side].start,
+            });
+        }
+    }
+    moves
+}
+
+// holds on to the whole diff so that lines moved between hunks and files can be found
+pub struct MovedPrinter<'a, T: Write> {
+    inner: TerminalPrinter<'a, T>,
+    events: Vec<Buffered>,
+}
+
+impl<'a, T: Write> MovedPrinter<'a, T> {
+    pub fn new(inner: TerminalPrinter<'a, T>) -> Self {
+        Self{
+            inner,
+            events: vec![],
+        }
+    }
+
+    // prints the hunk in pieces around the moved lines
+    fn print_hunk(&mut self, tokeniser: &mut Tokeniser, hunk: &Hunk, line_numbers: [usize; 2], ops: &[Opcode], moves: &[Move]) -> Result<()> {
+        let mut start = [0, 0];
+        let ends = ops.iter().flat_map(|(_, left, right)| {
+            [left, right].into_iter().enumerate().filter_map(move |(side, lines)| {
+                let moved = moves.iter().find(|m| m.side == side && lines.contains(&m.lines.start))?;
+                let mut end = [left.end, right.start];
+                end[side] = moved.lines.start;
+                Some((end, Some(moved)))
+            })
+        }).chain(std::iter::once(([hunk.left.len(), hunk.right.len()], None)));
+
+        for (end, moved) in ends {
+            if start != end {
+                let mut piece = Hunk::new();
+                piece.left = hunk.left[start[0] .. end[0]].to_vec();
+                piece.right = hunk.right[start[1] .. end[1]].to_vec();
+                let line_numbers = [0, 1].map(|i| line_numbers[i] + start[i]);
+                self.inner.print(tokeniser, Event::Hunk{hunk: &mut piece, line_numbers, merge_markers: None})?;
+            }
+
+            if let Some(moved) = moved {
+                let side = moved.side;
+                let mut piece = Hunk::new();
+                *piece.get_mut(side) = hunk.get(side)[moved.lines.clone()].to_vec();
+                *piece.get_mut(1-side) = moved.other.clone();
+                let mut moved_line_numbers = [0, 0];
+                moved_line_numbers[side] = line_numbers[side] + moved.lines.start;
+                moved_line_numbers[1-side] = moved.other_lineno;
+                self.inner.print_moved(tokeniser, &mut piece, moved_line_numbers, side)?;
+
+                start = end;
+                start[side] = moved.lines.end;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T: Write> Printer for MovedPrinter<'_, T> {
+    fn print(&mut self, _tokeniser: &mut Tokeniser, event: Event) -> Result<()> {
+        self.events.push(Buffered::new(event));
+        Ok(())
+    }
+
+    fn finish(&mut self, tokeniser: &mut Tokeniser) -> Result<()> {
+        let mut events = std::mem::take(&mut self.events);
+
+        // the line by line diff of each hunk
+        let ops: Vec<_> = events.iter().map(|event| {
+            let (hunk, _) = event.hunk().filter(|(h, _)| !h.is_empty())?;
+            Some(FileDiffer::new(&hunk.left, &hunk.right, tokeniser, None).get_opcodes())
+        }).collect();
+        let mut moves = find_moves(&events, &ops);
+
+        for (i, event) in events.iter_mut().enumerate() {
+            if let Some(moves) = moves.remove(&i)
+            && let Some((hunk, line_numbers)) = event.hunk() {
+                self.print_hunk(tokeniser, hunk, line_numbers, ops[i].as_deref().unwrap(), &moves)?;
+            } else {
+                let event = event.replay(tokeniser);
+                self.inner.print(tokeniser, event)?;
+            }
+        }
+        self.inner.finish(tokeniser)
+    }
+}
@@ -0,0 +1,422 @@
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::io::{BufWriter, Write};
+use std::rc::Rc;
+use anyhow::Result;
+use bstr::{BString, ByteSlice};
+use super::hunk::{Hunk, MergeMarkers};
+use super::style::{self, Style, StyleOpts};
+use super::syntax::{Highlighter, Language};
+use super::hyperlink::Hyperlinks;
+use super::tokeniser::Tokeniser;
+use super::invisibles::{self, Show};
+use super::types::*;
+
+// the things found in a diff, in the order they are found
+pub enum Event<'a> {
+    // diff --git a/file b/file
+    DiffHeader{header: &'a [u8], filename1: &'a [u8], filename2: &'a [u8], trailer: &'a [u8]},
+    // ---/+++ or rename from/to lines
+    // prefixes are the a/ and b/ that git puts before the names, which have been taken off
+    Filename{left: Option<Bytes<'a>>, right: Option<Bytes<'a>>, rename: bool, prefixes: [&'a [u8]; 2]},
+    // @@ -a,b +c,d @@ context
+    HunkHeader{header: &'a [u8], context: &'a [u8]},
+    // @@@ -a,b -c,d +e,f @@@ context
+    MergeHunkHeader{header: &'a [u8], context: &'a [u8]},
+    // 1,2c3,4
+    NormalHunkHeader{line: &'a [u8]},
+    Hunk{hunk: &'a mut Hunk, line_numbers: [usize; 2], merge_markers: Option<&'a MergeMarkers>},
+    // a context line when using --exact
+    Context{line_numbers: [usize; 2], line: &'a [u8]},
+    Commit{line: Bytes<'a>},
+    Index{line: Bytes<'a>},
+    // Only in DIR: name
+    OnlyIn{side: usize, message: &'a [u8]},
+    Message{message: &'a [u8]},
+    // anything else
+    Text{line: &'a [u8]},
+    // passed through without any styling
+    Raw{line: &'a [u8]},
+}
+
+impl<'a> Event<'a> {
+    // the name of the file that the hunks after this event are in
+    pub fn filename(&self) -> Option<&'a [u8]> {
+        match self {
+            Self::DiffHeader{filename2, ..} => Some(filename2.strip_prefix(b"b/").unwrap_or(filename2)),
+            // deleted files have no new name
+            Self::Filename{left, right, ..} => right.filter(|f| f.trim_ascii() != b"/dev/null").or(*left).map(|f| f.as_bytes()),
+            _ => None,
+        }
+    }
+}
+
+// an event with its data copied, so that it can be printed at the end
+pub(crate) enum Buffered {
+    DiffHeader{header: BString, filename1: BString, filename2: BString, trailer: BString},
+    Filename{left: Option<BString>, right: Option<BString>, rename: bool, prefixes: [BString; 2]},
+    HunkHeader{header: BString, context: BString},
+    MergeHunkHeader{header: BString, context: BString},
+    NormalHunkHeader{line: BString},
+    Hunk{hunk: Hunk, line_numbers: [usize; 2], merge_markers: Option<MergeMarkers>},
+    Context{line_numbers: [usize; 2], line: BString},
+    Commit{line: BString},
+    Index{line: BString},
+    OnlyIn{side: usize, message: BString},
+    Message{message: BString},
+    Text{line: BString},
+    Raw{line: BString},
+}
+
+impl Buffered {
+    pub(crate) fn new(event: Event) -> Self {
+        match event {
+            Event::DiffHeader{header, filename1, filename2, trailer} => Self::DiffHeader{
+                header: header.into(),
+                filename1: filename1.into(),
+                filename2: filename2.into(),
+                trailer: trailer.into(),
+            },
+            Event::Filename{left, right, rename, prefixes} => Self::Filename{
+                left: left.map(|l| l.into()),
+                right: right.map(|r| r.into()),
+                rename,
+                prefixes: prefixes.map(|p| p.into()),
+            },
+            Event::HunkHeader{header, context} => Self::HunkHeader{header: header.into(), context: context.into()},
+            Event::MergeHunkHeader{header, context} => Self::MergeHunkHeader{header: header.into(), context: context.into()},
+            Event::NormalHunkHeader{line} => Self::NormalHunkHeader{line: line.into()},
+            Event::Hunk{hunk, line_numbers, merge_markers} => Self::Hunk{hunk: hunk.clone(), line_numbers, merge_markers: merge_markers.cloned()},
+            Event::Context{line_numbers, line} => Self::Context{line_numbers, line: line.into()},
+            Event::Commit{line} => Self::Commit{line: line.into()},
+            Event::Index{line} => Self::Index{line: line.into()},
+            Event::OnlyIn{side, message} => Self::OnlyIn{side, message: message.into()},
+            Event::Message{message} => Self::Message{message: message.into()},
+            Event::Text{line} => Self::Text{line: line.into()},
+            Event::Raw{line} => Self::Raw{line: line.into()},
+        }
+    }
+
+    pub(crate) fn as_event(&mut self) -> Event<'_> {
+        match self {
+            Self::DiffHeader{header, filename1, filename2, trailer} => Event::DiffHeader{header, filename1, filename2, trailer},
+            Self::Filename{left, right, rename, prefixes} => Event::Filename{
+                left: left.as_ref().map(|l| l.as_bstr()),
+                right: right.as_ref().map(|r| r.as_bstr()),
+                rename: *rename,
+                prefixes: [&prefixes[0], &prefixes[1]],
+            },
+            Self::HunkHeader{header, context} => Event::HunkHeader{header, context},
+            Self::MergeHunkHeader{header, context} => Event::MergeHunkHeader{header, context},
+            Self::NormalHunkHeader{line} => Event::NormalHunkHeader{line},
+            Self::Hunk{hunk, line_numbers, merge_markers} => Event::Hunk{hunk, line_numbers: *line_numbers, merge_markers: merge_markers.as_ref()},
+            Self::Context{line_numbers, line} => Event::Context{line_numbers: *line_numbers, line},
+            Self::Commit{line} => Event::Commit{line: line.as_bstr()},
+            Self::Index{line} => Event::Index{line: line.as_bstr()},
+            Self::OnlyIn{side, message} => Event::OnlyIn{side: *side, message},
+            Self::Message{message} => Event::Message{message},
+            Self::Text{line} => Event::Text{line},
+            Self::Raw{line} => Event::Raw{line},
+        }
+    }
+
+    // the event to print at the end, picking the words for its file again as they were when it was first printed
+    pub(crate) fn replay(&mut self, tokeniser: &mut Tokeniser) -> Event<'_> {
+        let event = self.as_event();
+        if let Some(filename) = event.filename() {
+            tokeniser.set_filename(filename);
+        }
+        event
+    }
+
+    pub(crate) fn hunk(&self) -> Option<(&Hunk, [usize; 2])> {
+        match self {
+            Self::Hunk{hunk, line_numbers, merge_markers: None} => Some((hunk, *line_numbers)),
+            _ => None,
+        }
+    }
+}
+
+pub trait Printer {
+    fn print(&mut self, tokeniser: &mut Tokeniser, event: Event) -> Result<()>;
+    // called once at the end of the output
+    fn finish(&mut self, tokeniser: &mut Tokeniser) -> Result<()>;
+}
+
+// counts the lines written through it, including a last line with no newline
+pub struct LineCounter<T: Write> {
+    inner: T,
+    lines: Rc<Cell<usize>>,
+    at_line_start: bool,
+}
+
+impl<T: Write> LineCounter<T> {
+    pub fn new(inner: T, lines: Rc<Cell<usize>>) -> Self {
+        Self{inner, lines, at_line_start: true}
+    }
+}
+
+impl<T: Write> Write for LineCounter<T> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        let buf = &buf[..len];
+        if !buf.is_empty() {
+            let mut lines = buf.split_inclusive(|&c| c == b'\n').count();
+            if !self.at_line_start {
+                // the first one continues the previous line
+                lines -= 1;
+            }
+            self.lines.set(self.lines.get() + lines);
+            self.at_line_start = buf.ends_with(b"\n");
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// the line without its newline, for outputs that end lines themselves
+pub(crate) fn trim_newline(string: &[u8]) -> &[u8] {
+    string.strip_suffix(b"\n").unwrap_or(string)
+}
+
+// the text with the chars that replace returns something for replaced, for the json and html outputs
+pub(crate) fn escape_with(string: &[u8], replace: impl Fn(char) -> Option<Cow<'static, str>>) -> String {
+    let mut escaped = String::with_capacity(string.len());
+    for c in string.chars() {
+        match replace(c) {
+            Some(r) => escaped.push_str(&r),
+            None => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// the last line number in a hunk header
+// @@ headers give the start and length of each side, normal and context diffs give the start and end
+fn last_lineno(header: &[u8], unified: bool) -> usize {
+    crate::regexes::byte_regex!(r"(\d+)(?:,(\d+))?", |r| {
+        r.captures_iter(header).map(|c| {
+            let num = |i| c.get(i).and_then(|m| std::str::from_utf8(m.as_bytes()).ok()?.parse::<usize>().ok());
+            match (num(1).unwrap_or(0), num(2)) {
+                (start, Some(len)) if unified => start + len.saturating_sub(1),
+                (_, Some(end)) => end,
+                (start, None) => start,
+            }
+        }).max().unwrap_or(0)
+    })
+}
+
+// fit line numbers up to last, shrinking to fit if this is a new hunk
+fn fit_lineno_width(style: &mut Style, fixed: Option<usize>, last: usize, new_hunk: bool) {
+    let width = fixed.unwrap_or_else(|| style::lineno_width(last));
+    if new_hunk || width > style.lineno_width {
+        style.lineno_width = width;
+    }
+}
+
+pub struct TerminalPrinter<'a, T: Write> {
+    pub stdout: BufWriter<T>,
+    pub color: bool,
+    pub style: Style<'a>,
+    pub style_opts: &'a StyleOpts,
+    // syntax highlight files in the languages we know
+    pub syntax: bool,
+    pub hyperlinks: Option<Hyperlinks>,
+    // use this many columns for line numbers instead of working it out for each hunk
+    pub lineno_width: Option<usize>,
+    // the url of the current file
+    url: Option<String>,
+    // whether the current file is from a git diff, which names files relative to the top of the repo
+    git: bool,
+    diff_trailing_ws_pat: Vec<u8>,
+}
+
+impl<'a, T: Write> TerminalPrinter<'a, T> {
+    pub fn new(stdout: T, color: bool, style: Style<'a>, style_opts: &'a StyleOpts) -> Self {
+        let mut diff_trailing_ws_pat = regex::escape(&style_opts.diff_trailing_ws).into_bytes();
+        diff_trailing_ws_pat.extend(b"$0");
+
+        Self{
+            stdout: BufWriter::new(stdout),
+            color,
+            style,
+            style_opts,
+            syntax: false,
+            hyperlinks: None,
+            lineno_width: None,
+            url: None,
+            git: false,
+            diff_trailing_ws_pat,
+        }
+    }
+
+    fn print_message(&mut self, style: Option<&str>, message: &[u8]) -> Result<()> {
+        if self.color {
+            self.style_opts.print_background(&mut self.stdout)?;
+            if let Some(style) = style {
+                self.stdout.write_all(style.as_bytes())?;
+            }
+        }
+        self.stdout.write_all(message)?;
+        if self.color && style.is_some() {
+            self.stdout.write_all(style::RESET)?;
+        }
+        self.stdout.write_all(b"\n")?;
+        Ok(())
+    }
+
+    // prints one side of lines that were moved, with the changes from the other copy highlighted
+    pub fn print_moved(&mut self, tokeniser: &mut Tokeniser, hunk: &mut Hunk, line_numbers: [usize; 2], side: usize) -> Result<()> {
+        let style = Style{
+            show_both: true,
+            inline: false,
+            side: Some(side),
+            diff_matching: [(*self.style_opts.diff_moved_left).into(), (*self.style_opts.diff_moved_right).into()],
+            diff_non_matching: [(*self.style_opts.diff_moved_non_matching_left).into(), (*self.style_opts.diff_moved_non_matching_right).into()],
+            hyperlink: self.url.as_deref(),
+            ..self.style
+        };
+        hunk.print(&mut self.stdout, tokeniser, line_numbers, None, style, self.style_opts)
+    }
+}
+
+impl<T: Write> Printer for TerminalPrinter<'_, T> {
+    fn print(&mut self, tokeniser: &mut Tokeniser, event: Event) -> Result<()> {
+        let stdout = &mut self.stdout;
+        let style_opts = self.style_opts;
+        let filename = event.filename();
+
+        match event {
+            Event::DiffHeader{header, filename1, filename2, trailer} => {
+                if self.syntax {
+                    self.style.syntax = filename.and_then(Language::from_filename);
+                }
+                self.git = matches!(header, b"diff --git" | b"diff --cc");
+                if let Some(hyperlinks) = &self.hyperlinks {
+                    let filename = if self.git { filename2.strip_prefix(b"b/").unwrap_or(filename2) } else { filename2 };
+                    self.url = hyperlinks.file_url(filename, self.git);
+                }
+                Hunk::print_diff_header(stdout, style_opts, header, filename1, filename2, trailer)?;
+            },
+            Event::Filename{left, right, rename, prefixes} => {
+                if self.syntax {
+                    self.style.syntax = filename.and_then(Language::from_filename);
+                }
+                if let Some(hyperlinks) = &self.hyperlinks {
+                    let git = self.git || prefixes.iter().any(|p| !p.is_empty());
+                    self.url = right.and_then(|f| hyperlinks.file_url(f, git));
+                }
+                let style = Style{hyperlink: self.url.as_deref(), ..self.style};
+                let prefix = if rename {
+                    ("rename from\t", "rename to\t", "rename from/to\t")
+                } else {
+                    (&*style_opts.filename_sign_left, &*style_opts.filename_sign_right, &*style_opts.filename_sign)
+                };
+                Hunk::print_filename(stdout, left, right, prefix, style, style_opts)?;
+            },
+            Event::HunkHeader{header, context} => {
+                fit_lineno_width(&mut self.style, self.lineno_width, last_lineno(header, true), true);
+                Hunk::print_header(stdout, style_opts, header, context)?;
+            },
+            Event::MergeHunkHeader{header, context} => {
+                fit_lineno_width(&mut self.style, self.lineno_width, last_lineno(header, true), true);
+                style_opts.print_background(stdout)?;
+                stdout.write_all(style_opts.header.as_bytes())?;
+                stdout.write_all(header)?;
+                stdout.write_all(b" ")?;
+                stdout.write_all(style_opts.context.as_bytes())?;
+                stdout.write_all(context)?;
+                stdout.write_all(style::RESET)?;
+                stdout.write_all(b"\n")?;
+            },
+            Event::NormalHunkHeader{line} => {
+                fit_lineno_width(&mut self.style, self.lineno_width, last_lineno(line, false), true);
+                style_opts.print_background(stdout)?;
+                stdout.write_all(style_opts.header.as_bytes())?;
+                stdout.write_all(line)?;
+                stdout.write_all(style::RESET)?;
+            },
+            Event::Hunk{hunk, line_numbers, merge_markers} => {
+                // in case the header did not say, e.g. the second half of a context diff
+                let last = [0, 1].map(|i| line_numbers[i] + hunk.get(i).len().saturating_sub(1));
+                fit_lineno_width(&mut self.style, self.lineno_width, last[0].max(last[1]), false);
+                let style = Style{hyperlink: self.url.as_deref(), ..self.style};
+                hunk.print(stdout, tokeniser, line_numbers, merge_markers, style, style_opts)?;
+            },
+            Event::Context{line_numbers, line} => {
+                style_opts.print_background(stdout)?;
+                if self.style.line_numbers {
+                    stdout.write_all(style::format_lineno(
+                            line_numbers,
+                            Some(&style_opts.lineno), Some(&style_opts.lineno),
+                            None,
+                            self.style.lineno_width,
+                            self.url.as_deref(),
+                    ).as_ref())?;
+                }
+                if self.style.signs {
+                    stdout.write_all(style::SIGN[2])?;
+                }
+                stdout.write_all(style_opts.diff_context.as_bytes())?;
+
+                let show = Show::new(self.style.show_invisibles, false);
+                let context = style_opts.diff_context.as_bytes();
+                let mut highlighted = vec![];
+                let line = if let Some(language) = self.style.syntax {
+                    for (word, class) in Highlighter::new(language).line(line) {
+                        if let Some(class) = class {
+                            let syntax = style_opts.syntax(class).as_bytes();
+                            highlighted.extend_from_slice(syntax);
+                            highlighted.extend_from_slice(&invisibles::replace(word, show, style_opts, &[context, syntax]));
+                            highlighted.extend_from_slice(context);
+                        } else {
+                            highlighted.extend_from_slice(&invisibles::replace(word, show, style_opts, &[context]));
+                        }
+                    }
+                    Cow::Owned(highlighted)
+                } else {
+                    invisibles::replace(line, show, style_opts, &[context])
+                };
+                let line = &*line;
+                stdout.write_all(&crate::regexes::byte_regex!(r"\s+\n".replace_all(line, &self.diff_trailing_ws_pat)))?;
+            },
+            Event::Commit{line} => {
+                style_opts.print_background(stdout)?;
+                stdout.write_all(style_opts.commit.as_bytes())?;
+                stdout.write_all(&style::strip_style(line, format!("$0{}", style_opts.commit).as_bytes()))?;
+                stdout.write_all(style::RESET)?;
+            },
+            Event::Index{line} => {
+                style_opts.print_background(stdout)?;
+                stdout.write_all(&style::strip_style(line, format!("$0{}", style::DIFF_HEADER).as_bytes()))?;
+            },
+            Event::OnlyIn{side, message} => {
+                let style = if side == 0 { &style_opts.only_in_left } else { &style_opts.only_in_right };
+                self.print_message(Some(style), message)?;
+            },
+            Event::Message{message} => {
+                self.print_message(None, message)?;
+            },
+            Event::Text{line} => {
+                style_opts.print_background(stdout)?;
+                stdout.write_all(line)?;
+            },
+            Event::Raw{line} => {
+                for line in line.lines_with_terminator() {
+                    // changed lines still get their bidi controls shown, without colour
+                    let changed = line.first().is_some_and(|c| b"+-<>".contains(c));
+                    stdout.write_all(&invisibles::replace_plain(line, Show::new(self.style.show_invisibles, changed)))?;
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, _tokeniser: &mut Tokeniser) -> Result<()> {
+        self.stdout.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,245 @@
+use anyhow::Result;
+use bstr::{BString, ByteSlice};
+use unicode_width::UnicodeWidthStr;
+use super::printer::{Buffered, Event, Printer};
+use super::tokeniser::Tokeniser;
+use super::hunk::Hunk;
+use super::DiffBlock;
+
+// lines changed in one file
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Stat {
+    pub added: usize,
+    pub removed: usize,
+    // lines that were only partly changed
+    pub changed: usize,
+}
+
+impl Stat {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    // counts the lines in one block
+    pub fn add_block(&mut self, block: &DiffBlock) {
+        let [(removed, left_changed), (added, right_changed)] = [0, 1].map(|i| count_lines(block, i));
+        self.removed += removed;
+        self.added += added;
+        // a changed line may have been split or joined, so count whichever side has more
+        self.changed += left_changed.max(right_changed);
+    }
+}
+
+// the lines on one side of a block that are all changed, and those that are partly changed
+fn count_lines(block: &DiffBlock, i: usize) -> (usize, usize) {
+    let mut counts = (0, 0);
+    // whether the current line has any matching text, and any non matching text
+    let mut line = (false, false);
+
+    let mut end_line = |line: &mut (bool, bool)| {
+        match *line {
+            (false, true) => counts.0 += 1,
+            (true, true) => counts.1 += 1,
+            _ => (),
+        }
+        *line = (false, false);
+    };
+
+    for part in block.parts.iter() {
+        for piece in part.get(i).text.split_inclusive(|&c| c == b'\n') {
+            if part.matches {
+                // matching indentation or newlines does not make it the same line
+                line.0 |= !piece.trim_ascii().is_empty();
+            } else {
+                line.1 = true;
+            }
+            if piece.ends_with(b"\n") {
+                end_line(&mut line);
+            }
+        }
+    }
+    // no newline at end of file
+    end_line(&mut line);
+    counts
+}
+
+// a diff passed through as is, e.g. when colour is off, counted from its unified or normal diff lines
+#[derive(Default)]
+struct RawDiff {
+    hunk: Hunk,
+    // the lines left on each side of the current unified hunk
+    remaining: Option<[usize; 2]>,
+    // which sides the last line was on
+    last: [bool; 2],
+}
+
+// counts the changes in each file and prints a table of them
+// either at the end, or at the start by holding back the diff until it has all been read
+pub struct StatPrinter<'a> {
+    inner: Box<dyn Printer + 'a>,
+    up_front: bool,
+    files: Vec<(BString, Stat)>,
+    events: Vec<Buffered>,
+    raw: RawDiff,
+}
+
+impl<'a> StatPrinter<'a> {
+    pub fn new(inner: Box<dyn Printer + 'a>, up_front: bool) -> Self {
+        Self{inner, up_front, files: vec![], events: vec![], raw: RawDiff::default()}
+    }
+
+    fn set_filename(&mut self, filename: &[u8]) {
+        let filename = filename.split(|&c| c == b'\t').next().unwrap_or(filename).trim_ascii().into();
+        match self.files.last_mut() {
+            // the same file may be named more than once, e.g. by diff --git and then by +++
+            Some((name, stat)) if stat.is_empty() => *name = filename,
+            _ => self.files.push((filename, Stat::default())),
+        }
+    }
+
+    fn count(&mut self, tokeniser: &mut Tokeniser, event: &Event) {
+        if let Some(filename) = event.filename() {
+            self.set_filename(filename);
+        } else if let Event::Hunk{hunk, line_numbers, ..} = event {
+            self.add_hunk(tokeniser, hunk, *line_numbers);
+        } else if let Event::Raw{line} = event {
+            for line in line.lines_with_terminator() {
+                self.count_raw(tokeniser, line);
+            }
+        }
+    }
+
+    fn add_hunk(&mut self, tokeniser: &mut Tokeniser, hunk: &Hunk, line_numbers: [usize; 2]) {
+        if self.files.is_empty() {
+            self.files.push(Default::default());
+        }
+        let stat = &mut self.files.last_mut().unwrap().1;
+        for block in super::diff_lines_with_words(&hunk.left, &hunk.right, line_numbers, tokeniser.current_words().clone()) {
+            stat.add_block(&block);
+        }
+    }
+
+    fn flush_raw(&mut self, tokeniser: &mut Tokeniser) {
+        let hunk = std::mem::take(&mut self.raw.hunk);
+        self.add_hunk(tokeniser, &hunk, [1, 1]);
+    }
+
+    fn count_raw(&mut self, tokeniser: &mut Tokeniser, line: &[u8]) {
+        if line.starts_with(b"\\") {
+            // no newline at end of file
+            for (i, last) in self.raw.last.into_iter().enumerate() {
+                if last && let Some(line) = self.raw.hunk.get_mut(i).last_mut() && line.ends_with(b"\n") {
+                    line.pop();
+                }
+            }
+            return
+        }
+
+        if let Some(remaining) = &mut self.raw.remaining && *remaining != [0, 0] {
+            // context lines are part of the hunk, as when the diff is parsed
+            self.raw.last = match line.first() {
+                Some(b'-') => [true, false],
+                Some(b'+') => [false, true],
+                _ => [true, true],
+            };
+            for (i, last) in self.raw.last.into_iter().enumerate() {
+                if last {
+                    remaining[i] = remaining[i].saturating_sub(1);
+                    self.raw.hunk.get_mut(i).push(line.get(1..).unwrap_or_default().into());
+                }
+            }
+            return
+        }
+        self.raw.remaining = None;
+
+        if let Some(captures) = byte_regex!(r"^@@ -\d+(,(?<left>\d+))? \+\d+(,(?<right>\d+))? @@".captures(line)) {
+            self.flush_raw(tokeniser);
+            let count = |name| captures.name(name).map_or(Some(1), |n| n.as_bytes().to_str().ok()?.parse().ok());
+            self.raw.remaining = Some([count("left").unwrap_or(0), count("right").unwrap_or(0)]);
+        } else if let Some(sign) = line.get(..2) && (sign == b"< " || sign == b"> ") {
+            let side = if sign == b"< " { 0 } else { 1 };
+            if side == 0 && self.raw.last[1] {
+                // a new change in a normal diff without a hunk header between them
+                self.flush_raw(tokeniser);
+            }
+            self.raw.last = [side == 0, side == 1];
+            self.raw.hunk.get_mut(side).push(line[2..].into());
+        } else if line.trim_ascii_end() == b"---" {
+            // the separator between the two sides of a normal diff hunk
+        } else {
+            self.flush_raw(tokeniser);
+            let filename = match line.strip_prefix(b"+++ ") {
+                Some(filename) => Some(filename.strip_prefix(b"b/").unwrap_or(filename)),
+                None => byte_regex!(r"^diff( --git)? .* (b/)?(?<filename>\S+)\s*$".captures(line))
+                    .map(|captures| captures.name("filename").unwrap().as_bytes()),
+            };
+            if let Some(filename) = filename && filename.trim_ascii() != b"/dev/null" {
+                tokeniser.set_filename(filename);
+                self.set_filename(filename);
+            }
+        }
+    }
+
+    fn print_table(&mut self, tokeniser: &mut Tokeniser) -> Result<()> {
+        let files: Vec<_> = self.files.iter().filter(|(_, stat)| !stat.is_empty()).collect();
+        let total = files.iter().fold(Stat::default(), |total, (_, stat)| Stat{
+            added: total.added + stat.added,
+            removed: total.removed + stat.removed,
+            changed: total.changed + stat.changed,
+        });
+
+        let name_width = files.iter().map(|(name, _)| name.to_str_lossy().width()).max().unwrap_or(0);
+        let width = |n: usize| n.to_string().len();
+        let widths = [width(total.changed), width(total.added), width(total.removed)];
+
+        let mut lines = vec![];
+        for (name, stat) in files.iter() {
+            let padding = " ".repeat(name_width - name.to_str_lossy().width());
+            let line = format!(
+                " {name}{padding} | ~{:<w0$} +{:<w1$} -{:<w2$}",
+                stat.changed, stat.added, stat.removed,
+                w0 = widths[0], w1 = widths[1], w2 = widths[2],
+            );
+            lines.push(format!("{}\n", line.trim_end()));
+        }
+        lines.push(format!(
+            " {} file{} changed, {} line{} partly changed, {} added, {} removed\n",
+            files.len(), if files.len() == 1 { "" } else { "s" },
+            total.changed, if total.changed == 1 { "" } else { "s" },
+            total.added, total.removed,
+        ));
+
+        for line in lines {
+            self.inner.print(tokeniser, Event::Text{line: line.as_bytes()})?;
+        }
+        Ok(())
+    }
+}
+
+impl Printer for StatPrinter<'_> {
+    fn print(&mut self, tokeniser: &mut Tokeniser, event: Event) -> Result<()> {
+        self.count(tokeniser, &event);
+        if self.up_front {
+            self.events.push(Buffered::new(event));
+            Ok(())
+        } else {
+            self.inner.print(tokeniser, event)
+        }
+    }
+
+    fn finish(&mut self, tokeniser: &mut Tokeniser) -> Result<()> {
+        self.flush_raw(tokeniser);
+        if self.up_front {
+            self.print_table(tokeniser)?;
+            self.inner.print(tokeniser, Event::Text{line: b"\n"})?;
+            for mut event in std::mem::take(&mut self.events) {
+                let event = event.replay(tokeniser);
+                self.inner.print(tokeniser, event)?;
+            }
+        } else {
+            self.inner.print(tokeniser, Event::Text{line: b"\n"})?;
+            self.print_table(tokeniser)?;
+        }
+        self.inner.finish(tokeniser)
+    }
+}
@@ -1,4 +1,11 @@
+use std::borrow::Cow;
+use std::io::{BufWriter, Write};
+use anyhow::Result;
 use super::types::*;
+use super::style_parser::parse_style;
+use super::color_depth::ColorDepth;
+use super::syntax::{Class, Language};
+use super::hyperlink;
 
 #[derive(Copy, Clone, Debug)]
 pub struct Style<'a> {
@@ -6,6 +13,25 @@ pub struct Style<'a> {
     pub signs: bool,
     pub show_both: bool,
     pub inline: bool,
+    pub side_by_side: Option<usize>,
+    // one output line for every input line
+    pub preserve_lines: bool,
+    // print only this side of each block
+    pub side: Option<usize>,
+    // the language of the current file, if it is being syntax highlighted
+    pub syntax: Option<Language>,
+    // link the line numbers to this url, with {line} filled in
+    pub hyperlink: Option<&'a str>,
+    // columns for each line number
+    pub lineno_width: usize,
+    // highlight only the characters that changed in short, similar words
+    pub char_diff: bool,
+    // print lines that only changed their indentation once, with the change in the gutter
+    pub reindent: bool,
+    // print non matching whitespace as if it matched
+    pub ignore_space: Option<IgnoreSpace>,
+    // replace tabs, zero width spaces and other invisible characters with glyphs
+    pub show_invisibles: bool,
 
     pub diff_matching: [Bytes<'a>; 2],
     pub diff_matching_inline: Bytes<'a>,
@@ -19,6 +45,16 @@ impl std::default::Default for Style<'_> {
             signs: false,
             show_both: false,
             inline: false,
+            side_by_side: None,
+            preserve_lines: false,
+            side: None,
+            syntax: None,
+            hyperlink: None,
+            lineno_width: LINENO_WIDTH,
+            char_diff: false,
+            reindent: true,
+            ignore_space: None,
+            show_invisibles: false,
             diff_matching: [DIFF_MATCHING[0].into(), DIFF_MATCHING[1].into()],
             diff_matching_inline: DIFF_MATCHING_INLINE.into(),
             diff_non_matching: [DIFF_NON_MATCHING[0].into(), DIFF_NON_MATCHING[1].into()],
@@ -26,6 +62,15 @@ impl std::default::Default for Style<'_> {
     }
 }
 
+// which whitespace changes to ignore, like diff -b and -w
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum IgnoreSpace {
+    // changes in the amount of whitespace, and whitespace at the end of lines
+    Change,
+    // all whitespace
+    All,
+}
+
 macro_rules! concat_bytes {
     (_, $A:expr, $B:expr) => {{
         const LEN: usize = $A.len() + $B.len();
@@ -67,6 +112,13 @@ macro_rules! concat_str {
     }};
 }
 
+pub fn strip_style<'a>(string: Bytes<'a>, replace: &[u8]) -> Cow<'a, bstr::BStr> {
+    match crate::regexes::byte_regex!(r"\x1b\[[\d;]*m".replace_all(string, replace)) {
+        Cow::Borrowed(x) => Cow::Borrowed(x.into()),
+        Cow::Owned(x) => Cow::Owned(x.into()),
+    }
+}
+
 pub const RESET: &[u8]      = b"\x1b[0m";
 pub const PAINT_RIGHT: &str = "\x1b[K";
 pub const BOLD: &str        = "\x1b[1m";
@@ -108,6 +160,11 @@ pub const FILENAME_NON_MATCHING: [&str; 2] = [
     concat_str!(DIFF_NON_MATCHING[1], FILENAME_BG, "\x1b[1m"),
 ];
 
+pub const ONLY_IN: [&str; 2] = [
+    concat_str!(DIFF.0, BOLD),
+    concat_str!(DIFF.1, BOLD),
+];
+
 pub const DIFF_MATCHING: [&str; 2] = [
     "\x1b[0;38;2;220;190;210;48;2;35;20;20m",
     "\x1b[0;38;2;190;220;210;48;2;20;35;20m",
@@ -116,6 +173,14 @@ pub const DIFF_NON_MATCHING: [&str; 2] = [
     concat_str!(DIFF.0, "\x1b[1;48;2;80;30;30m"),
     concat_str!(DIFF.1, "\x1b[1;48;2;25;80;25m"),
 ];
+pub const DIFF_MOVED: [&str; 2] = [
+    "\x1b[0;38;2;215;195;235;48;2;35;20;45m",
+    "\x1b[0;38;2;190;215;235;48;2;15;30;45m",
+];
+pub const DIFF_MOVED_NON_MATCHING: [&str; 2] = [
+    "\x1b[0;1;35;48;2;75;35;95m",
+    "\x1b[0;1;36;48;2;25;65;95m",
+];
 pub const DIFF_INSERT: [&str; 2] = [
     "\x1b[4:3:58:5:10m",
     "\x1b[4:3;58;5;9m",
@@ -123,23 +188,264 @@ pub const DIFF_INSERT: [&str; 2] = [
 pub const DIFF_MATCHING_INLINE: &str = "\x1b[0;38;5;252m";
 pub const DIFF_CONTEXT: &str = LINENO;
 pub const DIFF_TRAILING_WS: &str = "\x1b[2;7m";
+pub const DIFF_REINDENT: &str = "\x1b[0;38;5;179m";
+pub const DIFF_IGNORED_SPACE: &str = "\x1b[0;48;5;236m";
+// added to the diff style, so the background shows through
+pub const DIFF_INVISIBLE: &str = "\x1b[2m";
+pub const DIFF_BIDI: &str = "\x1b[0;1;97;41m";
+
+// these only set the foreground so the diff backgrounds show through
+pub const SYNTAX_KEYWORD: &str = "\x1b[38;5;176m";
+pub const SYNTAX_STRING: &str = "\x1b[38;5;150m";
+pub const SYNTAX_COMMENT: &str = "\x1b[38;5;245m";
+pub const SYNTAX_CONSTANT: &str = "\x1b[38;5;180m";
+pub const SYNTAX_TYPE: &str = "\x1b[38;5;116m";
+
+// used until the line numbers are known
+pub const LINENO_WIDTH: usize = 4;
+
+// columns needed for the line numbers up to max
+pub fn lineno_width(max: usize) -> usize {
+    max.checked_ilog10().map_or(1, |n| n as usize + 1)
+}
+
+// pads out a line number, linking it to url if there is one
+fn pad_lineno(num: usize, width: usize, url: Option<&str>) -> String {
+    match (num, url) {
+        (0, _) => format!("{:<width$}", ""),
+        (num, Some(url)) => format!("{}{:<width$}{}", hyperlink::start(url, num), num, hyperlink::END),
+        (num, None) => format!("{:<width$}", num),
+    }
+}
+
+pub fn format_side_lineno(num: usize, style: &str, bar_style: &str, width: usize, url: Option<&str>) -> String {
+    format!("{}{}{} ", style, pad_lineno(num, width, url), bar_style)
+}
 
+// only the right line numbers are linked, as the left ones are for the old file
 pub fn format_lineno(
     [num1, num2]: [usize; 2],
     left_style: Option<&str>,
     right_style: Option<&str>,
     bar_style: Option<&str>,
+    width: usize,
+    url: Option<&str>,
 ) -> String {
-    let num1 = if num1 != 0 { Some(num1.to_string()) } else { None };
-    let num2 = if num2 != 0 { Some(num2.to_string()) } else { None };
-
     format!(
-        "{}{:<4}{}{}{:<4}{} ",
+        "{}{}{}{}{}{} ",
         left_style.unwrap_or(LINENO_DIFF.0),
-        num1.as_ref().map(|n| n.as_ref()).unwrap_or(""),
+        pad_lineno(num1, width, None),
         bar_style.unwrap_or(LINENO_BAR),
         right_style.unwrap_or(LINENO_DIFF.1),
-        num2.as_ref().map(|n| n.as_ref()).unwrap_or(""),
+        pad_lineno(num2, width, url),
         bar_style.unwrap_or(LINENO_BAR),
     )
 }
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct StyleOpts {
+    #[arg(long, default_value_t = HEADER.into(), value_parser = parse_style)]
+    pub header: Cow<'static, str>,
+    #[arg(long, default_value_t = COMMIT.into(), value_parser = parse_style)]
+    pub commit: Cow<'static, str>,
+    #[arg(long, default_value_t = BACKGROUND.into(), value_parser = parse_style)]
+    pub background: Cow<'static, str>,
+    #[arg(long, default_value_t = CONTEXT.into(), value_parser = parse_style)]
+    pub context: Cow<'static, str>,
+    #[arg(long, default_value_t = LINENO.into(), value_parser = parse_style)]
+    pub lineno: Cow<'static, str>,
+    #[arg(long, default_value_t = LINENO_DIFF.0.into(), value_parser = parse_style)]
+    pub lineno_left: Cow<'static, str>,
+    #[arg(long, default_value_t = LINENO_DIFF.1.into(), value_parser = parse_style)]
+    pub lineno_right: Cow<'static, str>,
+    // the bars and signs are the text to print as well as its style, so they are taken as they are
+    #[arg(long, default_value_t = LINENO_BAR.into())]
+    pub lineno_bar: Cow<'static, str>,
+
+    #[arg(long, default_value_t = LINENO_OUR_BAR.into())]
+    pub lineno_our_bar: Cow<'static, str>,
+    #[arg(long, default_value_t = LINENO_THEIR_BAR.into())]
+    pub lineno_their_bar: Cow<'static, str>,
+    #[arg(long, default_value_t = LINENO_MERGE_BAR.into())]
+    pub lineno_merge_bar: Cow<'static, str>,
+
+    #[arg(long, default_value_t = FILENAME.2.into(), value_parser = parse_style)]
+    pub filename: Cow<'static, str>,
+    #[arg(long, default_value_t = FILENAME.0.into(), value_parser = parse_style)]
+    pub filename_left: Cow<'static, str>,
+    #[arg(long, default_value_t = FILENAME.1.into(), value_parser = parse_style)]
+    pub filename_right: Cow<'static, str>,
+    #[arg(long, default_value_t = FILENAME_RENAME.into(), value_parser = parse_style)]
+    pub filename_rename: Cow<'static, str>,
+    #[arg(long, default_value_t = FILENAME_HEADER.0.into(), value_parser = parse_style)]
+    pub filename_header_left: Cow<'static, str>,
+    #[arg(long, default_value_t = FILENAME_HEADER.1.into(), value_parser = parse_style)]
+    pub filename_header_right: Cow<'static, str>,
+    #[arg(long, default_value_t = FILENAME_SIGN.2.into())]
+    pub filename_sign: Cow<'static, str>,
+    #[arg(long, default_value_t = FILENAME_SIGN.0.into())]
+    pub filename_sign_left: Cow<'static, str>,
+    #[arg(long, default_value_t = FILENAME_SIGN.1.into())]
+    pub filename_sign_right: Cow<'static, str>,
+    #[arg(long, default_value_t = FILENAME_NON_MATCHING[0].into(), value_parser = parse_style)]
+    pub filename_non_matching_left: Cow<'static, str>,
+    #[arg(long, default_value_t = FILENAME_NON_MATCHING[1].into(), value_parser = parse_style)]
+    pub filename_non_matching_right: Cow<'static, str>,
+
+    #[arg(long, default_value_t = ONLY_IN[0].into(), value_parser = parse_style)]
+    pub only_in_left: Cow<'static, str>,
+    #[arg(long, default_value_t = ONLY_IN[1].into(), value_parser = parse_style)]
+    pub only_in_right: Cow<'static, str>,
+
+    #[arg(long, default_value_t = DIFF_MATCHING[0].into(), value_parser = parse_style)]
+    pub diff_matching_left: Cow<'static, str>,
+    #[arg(long, default_value_t = DIFF_MATCHING[1].into(), value_parser = parse_style)]
+    pub diff_matching_right: Cow<'static, str>,
+
+    #[arg(long, default_value_t = DIFF_NON_MATCHING[0].into(), value_parser = parse_style)]
+    pub diff_non_matching_left: Cow<'static, str>,
+    #[arg(long, default_value_t = DIFF_NON_MATCHING[1].into(), value_parser = parse_style)]
+    pub diff_non_matching_right: Cow<'static, str>,
+
+    #[arg(long, default_value_t = DIFF_MOVED[0].into(), value_parser = parse_style)]
+    pub diff_moved_left: Cow<'static, str>,
+    #[arg(long, default_value_t = DIFF_MOVED[1].into(), value_parser = parse_style)]
+    pub diff_moved_right: Cow<'static, str>,
+    #[arg(long, default_value_t = DIFF_MOVED_NON_MATCHING[0].into(), value_parser = parse_style)]
+    pub diff_moved_non_matching_left: Cow<'static, str>,
+    #[arg(long, default_value_t = DIFF_MOVED_NON_MATCHING[1].into(), value_parser = parse_style)]
+    pub diff_moved_non_matching_right: Cow<'static, str>,
+
+    #[arg(long, default_value_t = DIFF_INSERT[0].into(), value_parser = parse_style)]
+    pub diff_insert_left: Cow<'static, str>,
+    #[arg(long, default_value_t = DIFF_INSERT[1].into(), value_parser = parse_style)]
+    pub diff_insert_right: Cow<'static, str>,
+
+    #[arg(long, default_value_t = DIFF_MATCHING_INLINE.into(), value_parser = parse_style)]
+    pub diff_matching_inline: Cow<'static, str>,
+    #[arg(long, default_value_t = DIFF_CONTEXT.into(), value_parser = parse_style)]
+    pub diff_context: Cow<'static, str>,
+    #[arg(long, default_value_t = DIFF_TRAILING_WS.into(), value_parser = parse_style)]
+    pub diff_trailing_ws: Cow<'static, str>,
+    #[arg(long, default_value_t = DIFF_REINDENT.into(), value_parser = parse_style)]
+    pub diff_reindent: Cow<'static, str>,
+    #[arg(long, default_value_t = DIFF_IGNORED_SPACE.into(), value_parser = parse_style)]
+    pub diff_ignored_space: Cow<'static, str>,
+    #[arg(long, default_value_t = DIFF_INVISIBLE.into(), value_parser = parse_style)]
+    pub diff_invisible: Cow<'static, str>,
+    #[arg(long, default_value_t = DIFF_BIDI.into(), value_parser = parse_style)]
+    pub diff_bidi: Cow<'static, str>,
+
+    #[arg(long, default_value_t = SYNTAX_KEYWORD.into(), value_parser = parse_style)]
+    pub syntax_keyword: Cow<'static, str>,
+    #[arg(long, default_value_t = SYNTAX_STRING.into(), value_parser = parse_style)]
+    pub syntax_string: Cow<'static, str>,
+    #[arg(long, default_value_t = SYNTAX_COMMENT.into(), value_parser = parse_style)]
+    pub syntax_comment: Cow<'static, str>,
+    #[arg(long, default_value_t = SYNTAX_CONSTANT.into(), value_parser = parse_style)]
+    pub syntax_constant: Cow<'static, str>,
+    #[arg(long, default_value_t = SYNTAX_TYPE.into(), value_parser = parse_style)]
+    pub syntax_type: Cow<'static, str>,
+}
+
+impl StyleOpts {
+    pub fn insert_background(&mut self) {
+        // insert the bg everywhere
+        if !self.background.is_empty() {
+            let mut replacement = "\x1b[0m".to_owned();
+            replacement += &self.background;
+            replacement += PAINT_RIGHT;
+            replacement += "\x1b[";
+
+            // everything but the background itself
+            let background = std::mem::take(&mut self.background);
+            let pat = "\x1b[0;";
+            for field in self.fields_mut() {
+                if field.contains(pat) {
+                    *field = Cow::from(field.replace(pat, &replacement));
+                }
+            }
+            self.background = background;
+        }
+    }
+
+    // all the styles
+    pub fn fields_mut(&mut self) -> [&mut Cow<'static, str>; 46] {
+        [
+            &mut self.header,
+            &mut self.commit,
+            &mut self.background,
+            &mut self.context,
+            &mut self.lineno,
+            &mut self.lineno_left,
+            &mut self.lineno_right,
+            &mut self.lineno_bar,
+            &mut self.lineno_our_bar,
+            &mut self.lineno_their_bar,
+            &mut self.lineno_merge_bar,
+            &mut self.filename,
+            &mut self.filename_left,
+            &mut self.filename_right,
+            &mut self.filename_rename,
+            &mut self.filename_header_left,
+            &mut self.filename_header_right,
+            &mut self.filename_sign,
+            &mut self.filename_sign_left,
+            &mut self.filename_sign_right,
+            &mut self.filename_non_matching_left,
+            &mut self.filename_non_matching_right,
+            &mut self.only_in_left,
+            &mut self.only_in_right,
+            &mut self.diff_matching_left,
+            &mut self.diff_matching_right,
+            &mut self.diff_non_matching_left,
+            &mut self.diff_non_matching_right,
+            &mut self.diff_moved_left,
+            &mut self.diff_moved_right,
+            &mut self.diff_moved_non_matching_left,
+            &mut self.diff_moved_non_matching_right,
+            &mut self.diff_insert_left,
+            &mut self.diff_insert_right,
+            &mut self.diff_matching_inline,
+            &mut self.diff_context,
+            &mut self.diff_trailing_ws,
+            &mut self.diff_reindent,
+            &mut self.diff_ignored_space,
+            &mut self.diff_invisible,
+            &mut self.diff_bidi,
+            &mut self.syntax_keyword,
+            &mut self.syntax_string,
+            &mut self.syntax_comment,
+            &mut self.syntax_constant,
+            &mut self.syntax_type,
+        ]
+    }
+
+    pub fn syntax(&self, class: Class) -> &str {
+        match class {
+            Class::Keyword => &self.syntax_keyword,
+            Class::String => &self.syntax_string,
+            Class::Comment => &self.syntax_comment,
+            Class::Constant => &self.syntax_constant,
+            Class::Type => &self.syntax_type,
+        }
+    }
+
+    // rewrite all the colours to the nearest ones the terminal supports
+    pub fn set_color_depth(&mut self, depth: ColorDepth) {
+        for field in self.fields_mut() {
+            let style = depth.downgrade(field);
+            if style != **field {
+                *field = Cow::from(style);
+            }
+        }
+    }
+
+    pub fn print_background<T: std::io::Write>(&self, stdout: &mut BufWriter<T>) -> Result<()> {
+        if !self.background.is_empty() {
+            stdout.write_all(self.background.as_bytes())?;
+            stdout.write_all(PAINT_RIGHT.as_bytes())?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,119 @@
+use std::borrow::Cow;
+
+const COLORS: [&str; 8] = ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+
+fn parse_color(word: &str, background: bool) -> Option<String> {
+    let (base, bright_base) = if background { (40, 100) } else { (30, 90) };
+
+    if word == "normal" {
+        return Some(String::new())
+    }
+    if word == "default" {
+        return Some((base + 9).to_string())
+    }
+    if let Some(i) = COLORS.iter().position(|c| *c == word) {
+        return Some((base + i).to_string())
+    }
+    if let Some(color) = word.strip_prefix("bright").map(|c| c.trim_start_matches('-'))
+    && let Some(i) = COLORS.iter().position(|c| *c == color) {
+        return Some((bright_base + i).to_string())
+    }
+    if let Ok(n) = word.parse::<u8>() {
+        return Some(format!("{};5;{}", base + 8, n))
+    }
+
+    let hex = word.strip_prefix('#')?;
+    if !hex.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return None
+    }
+    let rgb = match hex.len() {
+        // #rgb is short for #rrggbb
+        3 => [0, 1, 2].map(|i| u8::from_str_radix(&hex[i..i+1], 16).unwrap() * 17),
+        6 => [0, 2, 4].map(|i| u8::from_str_radix(&hex[i..i+2], 16).unwrap()),
+        _ => return None,
+    };
+    Some(format!("{};2;{};{};{}", base + 8, rgb[0], rgb[1], rgb[2]))
+}
+
+fn parse_attribute(word: &str) -> Option<&'static str> {
+    let (word, off) = match word.strip_prefix("no") {
+        Some(word) => (word.trim_start_matches('-'), true),
+        None => (word, false),
+    };
+
+    Some(match (word, off) {
+        ("bold", false) => "1",
+        ("dim", false) => "2",
+        ("bold" | "dim", true) => "22",
+        ("italic", false) => "3",
+        ("italic", true) => "23",
+        ("ul" | "underline", false) => "4",
+        ("ul" | "underline", true) => "24",
+        ("blink", false) => "5",
+        ("blink", true) => "25",
+        ("reverse", false) => "7",
+        ("reverse", true) => "27",
+        ("strike", false) => "9",
+        ("strike", true) => "29",
+        ("ul:single" | "underline:single", false) => "4:1",
+        ("ul:double" | "underline:double", false) => "4:2",
+        ("ul:curly" | "underline:curly", false) => "4:3",
+        ("ul:dotted" | "underline:dotted", false) => "4:4",
+        ("ul:dashed" | "underline:dashed", false) => "4:5",
+        _ => return None,
+    })
+}
+
+// parses a style like `bold #dcbed2 on #231414` into an escape sequence
+// the first colour is the foreground and the second is the background, like git
+// the style replaces any previous style unless it starts with a +
+pub fn parse_style(spec: &str) -> Result<Cow<'static, str>, String> {
+    if spec.is_empty() || spec.contains('\x1b') {
+        // already an escape sequence
+        return Ok(spec.to_owned().into())
+    }
+
+    let (spec, reset) = match spec.strip_prefix('+') {
+        Some(spec) => (spec, false),
+        None => (spec, true),
+    };
+
+    let mut codes = vec![];
+    if reset {
+        codes.push("0".to_owned());
+    }
+    // whether the foreground and background have been set
+    let mut colors = [false, false];
+    let mut on = false;
+    for word in spec.split_whitespace() {
+        let lower = word.to_ascii_lowercase();
+        if lower == "on" {
+            if on || colors[1] {
+                return Err("more than one background colour".into())
+            }
+            on = true;
+        } else if let Some(code) = parse_attribute(&lower) {
+            codes.push(code.to_owned());
+        } else if let Some(code) = parse_color(&lower, on || colors[0]) {
+            let i = if on || colors[0] { 1 } else { 0 };
+            if colors[i] {
+                return Err("too many colours".into())
+            }
+            colors[i] = true;
+            on = false;
+            if !code.is_empty() {
+                codes.push(code);
+            }
+        } else {
+            return Err(format!("unknown colour or attribute {word:?}"))
+        }
+    }
+
+    if on {
+        return Err("expected a colour after `on`".into())
+    }
+    if codes.is_empty() {
+        return Ok("".into())
+    }
+    Ok(format!("\x1b[{}m", codes.join(";")).into())
+}
@@ -0,0 +1,220 @@
+// a small syntax highlighter for a few languages
+// it works a word at a time on the words of a block, so it only knows about the current word and the next one
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Language {
+    Rust,
+    Python,
+    Shell,
+    Json,
+    Yaml,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Class {
+    Keyword,
+    String,
+    Comment,
+    // numbers, true, false etc
+    Constant,
+    // capitalised names and shell variables
+    Type,
+}
+
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern", "fn",
+    "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return",
+    "self", "Self", "static", "struct", "super", "trait", "type", "unsafe", "use", "where", "while",
+];
+const PYTHON_KEYWORDS: &[&str] = &[
+    "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del", "elif", "else",
+    "except", "finally", "for", "from", "global", "if", "import", "in", "is", "lambda", "nonlocal", "not",
+    "or", "pass", "raise", "return", "try", "while", "with", "yield",
+];
+const SHELL_KEYWORDS: &[&str] = &[
+    "case", "declare", "do", "done", "elif", "else", "esac", "exit", "export", "fi", "for", "function",
+    "if", "in", "local", "readonly", "return", "select", "shift", "then", "until", "unset", "while",
+];
+
+impl Language {
+    // picks the language from a file name
+    pub fn from_filename(filename: &[u8]) -> Option<Self> {
+        match crate::tokeniser::name_and_extension(filename) {
+            (_, b"rs") => Some(Self::Rust),
+            (_, b"py" | b"pyi") => Some(Self::Python),
+            (_, b"sh" | b"bash" | b"zsh") => Some(Self::Shell),
+            (b".bashrc" | b".bash_profile" | b".profile" | b".zshrc" | b"PKGBUILD", _) => Some(Self::Shell),
+            (_, b"json") => Some(Self::Json),
+            (_, b"yml" | b"yaml") => Some(Self::Yaml),
+            _ => None,
+        }
+    }
+
+    fn keywords(self) -> &'static [&'static str] {
+        match self {
+            Self::Rust => RUST_KEYWORDS,
+            Self::Python => PYTHON_KEYWORDS,
+            Self::Shell => SHELL_KEYWORDS,
+            Self::Json | Self::Yaml => &[],
+        }
+    }
+
+    fn constants(self) -> &'static [&'static str] {
+        match self {
+            Self::Rust | Self::Json => &["true", "false", "null"],
+            Self::Python => &["True", "False", "None"],
+            Self::Shell => &["true", "false"],
+            Self::Yaml => &["true", "false", "null", "yes", "no", "True", "False", "Null"],
+        }
+    }
+
+    fn quotes(self) -> &'static [u8] {
+        match self {
+            // single quotes are also lifetimes
+            Self::Rust | Self::Json => b"\"",
+            Self::Python | Self::Shell | Self::Yaml => b"\"'",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum State {
+    Normal,
+    String(u8),
+    // the word after a backslash in a string
+    Escape(u8),
+    LineComment,
+    BlockComment,
+    // the closing / of a block comment
+    EndBlockComment,
+    // the name after a $
+    Variable,
+}
+
+fn is_ident(c: Option<&u8>) -> bool {
+    c.is_some_and(|&c| c.is_ascii_alphanumeric() || c == b'_' || c >= 0x80)
+}
+
+pub struct Highlighter {
+    language: Language,
+    state: State,
+    // the class of the last word, if it could continue into this one
+    last: Option<Option<Class>>,
+    // whether the last word was whitespace or the start of a line
+    word_start: bool,
+}
+
+impl Highlighter {
+    pub fn new(language: Language) -> Self {
+        Self{language, state: State::Normal, last: None, word_start: true}
+    }
+
+    // the class of the next word, given the word after it on the same line
+    pub fn next(&mut self, word: &[u8], following: Option<&[u8]>) -> Option<Class> {
+        if word == b"\n" {
+            // strings and line comments stop at the end of a line
+            if self.state != State::BlockComment {
+                self.state = State::Normal;
+            }
+            self.last = None;
+            self.word_start = true;
+            return None
+        }
+
+        let word_start = std::mem::replace(&mut self.word_start, word.iter().all(u8::is_ascii_whitespace));
+        let class = match self.state {
+            State::Normal => self.normal(word, following, word_start),
+            State::LineComment => Some(Class::Comment),
+            State::BlockComment => {
+                if word == b"*" && following == Some(b"/") {
+                    self.state = State::EndBlockComment;
+                }
+                Some(Class::Comment)
+            },
+            State::EndBlockComment => {
+                self.state = State::Normal;
+                Some(Class::Comment)
+            },
+            State::String(quote) => {
+                if word == b"\\" && !(self.language == Language::Shell && quote == b'\'') {
+                    self.state = State::Escape(quote);
+                } else if word == [quote] {
+                    self.state = State::Normal;
+                }
+                Some(Class::String)
+            },
+            State::Escape(quote) => {
+                self.state = State::String(quote);
+                Some(Class::String)
+            },
+            State::Variable => {
+                self.state = State::Normal;
+                is_ident(word.first()).then_some(Class::Type)
+            },
+        };
+
+        // remember the class if the next word may be the rest of the same identifier
+        self.last = is_ident(word.last()).then_some(class);
+        class
+    }
+
+    fn normal(&mut self, word: &[u8], following: Option<&[u8]>, word_start: bool) -> Option<Class> {
+        let first = word.first();
+
+        if is_ident(first) && let Some(class) = self.last {
+            // the rest of an identifier that was split into several words
+            return class.filter(|c| matches!(c, Class::Type | Class::Constant))
+        }
+
+        match (self.language, word) {
+            (Language::Rust, b"/") if following == Some(b"/") => {
+                self.state = State::LineComment;
+                return Some(Class::Comment)
+            },
+            (Language::Rust, b"/") if following == Some(b"*") => {
+                self.state = State::BlockComment;
+                return Some(Class::Comment)
+            },
+            (Language::Python, b"#") => {
+                self.state = State::LineComment;
+                return Some(Class::Comment)
+            },
+            // otherwise it could be $# or a#b
+            (Language::Shell | Language::Yaml, b"#") if word_start => {
+                self.state = State::LineComment;
+                return Some(Class::Comment)
+            },
+            (Language::Shell, b"$") => {
+                if is_ident(following.and_then(|f| f.first())) {
+                    self.state = State::Variable;
+                }
+                return Some(Class::Type)
+            },
+            _ => (),
+        }
+
+        if let [c] = word && self.language.quotes().contains(c) {
+            self.state = State::String(*c);
+            return Some(Class::String)
+        }
+
+        let word = std::str::from_utf8(word).ok()?;
+        if first.is_some_and(u8::is_ascii_digit) || self.language.constants().contains(&word) {
+            Some(Class::Constant)
+        } else if self.language.keywords().contains(&word) && !is_ident(following.and_then(|f| f.first())) {
+            Some(Class::Keyword)
+        } else if matches!(self.language, Language::Rust | Language::Python) && first.is_some_and(u8::is_ascii_uppercase) {
+            Some(Class::Type)
+        } else {
+            None
+        }
+    }
+
+    // splits a whole line into words and their classes
+    pub fn line<'a>(&mut self, line: &'a [u8]) -> Vec<(&'a [u8], Option<Class>)> {
+        let words: Vec<&[u8]> = crate::regexes::byte_regex!(r"\w+|\s|." , |r| {
+            r.find_iter(line).map(|m| m.as_bytes()).collect()
+        });
+        words.iter().enumerate().map(|(i, word)| (*word, self.next(word, words.get(i+1).copied()))).collect()
+    }
+}
@@ -0,0 +1,92 @@
+pub fn width() -> Option<usize> {
+    for fd in [libc::STDOUT_FILENO, libc::STDERR_FILENO, libc::STDIN_FILENO] {
+        // SAFETY: winsize is plain old data and TIOCGWINSZ only writes into it
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        if unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut size) } == 0 && size.ws_col > 0 {
+            return Some(size.ws_col as usize)
+        }
+    }
+    std::env::var("COLUMNS").ok()?.parse().ok()
+}
+
+fn parse_osc11(response: &[u8]) -> Option<[f64; 3]> {
+    // e.g. \x1b]11;rgb:ffff/ffff/ffff\x1b\
+    let start = response.windows(4).position(|w| w == b"rgb:")? + 4;
+    let rgb = &response[start..];
+    let end = rgb.iter().position(|c| !c.is_ascii_hexdigit() && *c != b'/')?;
+    let rgb = std::str::from_utf8(&rgb[..end]).ok()?;
+
+    let mut color = [0.; 3];
+    let mut parts = rgb.split('/');
+    for c in color.iter_mut() {
+        let part = parts.next()?;
+        if part.is_empty() || part.len() > 4 {
+            return None
+        }
+        // each component has 1 to 4 hex digits
+        let max = (1u32 << (4 * part.len())) - 1;
+        *c = u32::from_str_radix(part, 16).ok()? as f64 / max as f64;
+    }
+    Some(color)
+}
+
+fn query_background(timeout: std::time::Duration) -> Option<[f64; 3]> {
+    use std::io::{Read, Write};
+    use std::os::fd::AsRawFd;
+
+    let mut tty = std::fs::OpenOptions::new().read(true).write(true).open("/dev/tty").ok()?;
+    let fd = tty.as_raw_fd();
+
+    // SAFETY: termios is plain old data and tcgetattr only writes into it
+    let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(fd, &mut termios) } != 0 {
+        return None
+    }
+    // turn off echo and line buffering so we can read the response
+    let mut raw = termios;
+    raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+    raw.c_cc[libc::VMIN] = 0;
+    raw.c_cc[libc::VTIME] = 0;
+    if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &raw) } != 0 {
+        return None
+    }
+
+    let mut response = vec![];
+    let result = (|| {
+        tty.write_all(b"\x1b]11;?\x1b\\").ok()?;
+        tty.flush().ok()?;
+
+        let deadline = std::time::Instant::now() + timeout;
+        loop {
+            let remaining = deadline.checked_duration_since(std::time::Instant::now())?;
+            let mut pollfd = libc::pollfd{fd, events: libc::POLLIN, revents: 0};
+            if unsafe { libc::poll(&mut pollfd, 1, remaining.as_millis() as _) } <= 0 {
+                return None
+            }
+            let mut buf = [0; 64];
+            let n = tty.read(&mut buf).ok()?;
+            response.extend_from_slice(&buf[..n]);
+            // the response ends with ST or BEL
+            if response.ends_with(b"\x1b\\") || response.ends_with(b"\x07") {
+                return parse_osc11(&response)
+            }
+        }
+    })();
+
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &termios) };
+    result
+}
+
+// whether the terminal has a light background, if it can be found out
+// query is whether to ask the terminal, which needs it to be ours to use
+pub fn is_light_background(query: bool) -> Option<bool> {
+    if query && let Some([r, g, b]) = query_background(std::time::Duration::from_millis(100)) {
+        let luminance = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+        return Some(luminance > 0.5)
+    }
+
+    // COLORFGBG is set by some terminals as e.g. 15;0
+    let colorfgbg = std::env::var("COLORFGBG").ok()?;
+    let bg: u8 = colorfgbg.rsplit(';').next()?.parse().ok()?;
+    Some(matches!(bg, 7 | 9..=15))
+}
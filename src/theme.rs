@@ -0,0 +1,131 @@
+#[derive(Clone, Copy, PartialEq, Debug, clap::ValueEnum)]
+pub enum Theme {
+    Dark,
+    Light,
+    HighContrast,
+    Auto,
+}
+
+// the dark theme is the defaults in style.rs
+const DARK: &[(&str, &str)] = &[];
+
+const LIGHT: &[(&str, &str)] = &[
+    ("header",                      "#0a6c8c"),
+    ("commit",                      "bold on #c6dcf0"),
+    ("background",                  ""),
+    ("context",                     "bold #6b5300 on #f2ebcf"),
+    ("lineno",                      "245"),
+    ("lineno-left",                 "#b3262b"),
+    ("lineno-right",                "#1e7a2e"),
+    ("lineno-bar",                  "\x1b[0;38;5;250m▏"),
+    ("lineno-our-bar",              "\x1b[0;38;5;136m("),
+    ("lineno-their-bar",            "\x1b[0;38;5;31m)"),
+    ("lineno-merge-bar",            "\x1b[0;38;5;127;1m|"),
+    ("filename",                    ""),
+    ("filename-left",               "#b3262b"),
+    ("filename-right",              "#1e7a2e"),
+    ("filename-rename",             "on 254"),
+    ("filename-header-left",        "bold #b3262b on 254"),
+    ("filename-header-right",       "bold #1e7a2e on 254"),
+    ("filename-sign",               "\x1b[0m\x1b[48;5;254m\x1b[7m###\x1b[27m "),
+    ("filename-sign-left",          "\x1b[0;38;2;179;38;43m\x1b[48;5;254m\x1b[7m---\x1b[27m "),
+    ("filename-sign-right",         "\x1b[0;38;2;30;122;46m\x1b[48;5;254m\x1b[7m+++\x1b[27m "),
+    ("filename-non-matching-left",  "bold #b3262b on #f5c4c4"),
+    ("filename-non-matching-right", "bold #1e7a2e on #c2ecc6"),
+    ("only-in-left",                "bold #b3262b"),
+    ("only-in-right",               "bold #1e7a2e"),
+    ("diff-matching-left",          "#5c3a3a on #fcecec"),
+    ("diff-matching-right",         "#2e4f38 on #eaf7ec"),
+    ("diff-non-matching-left",      "bold #b3262b on #f5c4c4"),
+    ("diff-non-matching-right",     "bold #1e7a2e on #c2ecc6"),
+    ("diff-moved-left",             "#5a3a6e on #f3ecf9"),
+    ("diff-moved-right",            "#2e4a66 on #eaf2fa"),
+    ("diff-moved-non-matching-left",  "bold #7a2e9e on #e2cdf2"),
+    ("diff-moved-non-matching-right", "bold #1c5a96 on #c9def5"),
+    ("diff-insert-left",            "\x1b[4:3:58:5:2m"),
+    ("diff-insert-right",           "\x1b[4:3;58;5;1m"),
+    ("diff-matching-inline",        "#3a3a3a"),
+    ("diff-context",                "245"),
+    ("diff-trailing-ws",            "+dim reverse"),
+    ("diff-reindent",               "#8a6d00"),
+    ("diff-ignored-space",          "on #e4e4e4"),
+    ("diff-invisible",              "+dim"),
+    ("diff-bidi",                   "bold brightwhite on red"),
+    ("syntax-keyword",              "+#8f2d91"),
+    ("syntax-string",               "+#3a6e1e"),
+    ("syntax-comment",              "+#8a8a8a"),
+    ("syntax-constant",             "+#a0522d"),
+    ("syntax-type",                 "+#1f5f8b"),
+];
+
+const HIGH_CONTRAST: &[(&str, &str)] = &[
+    ("header",                      "bold brightcyan"),
+    ("commit",                      "bold reverse"),
+    ("background",                  ""),
+    ("context",                     "bold brightyellow"),
+    ("lineno",                      "white"),
+    ("lineno-left",                 "bold brightred"),
+    ("lineno-right",                "bold brightgreen"),
+    ("lineno-bar",                  "\x1b[0;37m│"),
+    ("lineno-our-bar",              "\x1b[0;1;93m("),
+    ("lineno-their-bar",            "\x1b[0;1;96m)"),
+    ("lineno-merge-bar",            "\x1b[0;1;95m|"),
+    ("filename",                    ""),
+    ("filename-left",               "brightred"),
+    ("filename-right",              "brightgreen"),
+    ("filename-rename",             "reverse"),
+    ("filename-header-left",        "bold brightred"),
+    ("filename-header-right",       "bold brightgreen"),
+    ("filename-sign",               "\x1b[0;1m\x1b[7m###\x1b[27m "),
+    ("filename-sign-left",          "\x1b[0;1;91m\x1b[7m---\x1b[27m "),
+    ("filename-sign-right",         "\x1b[0;1;92m\x1b[7m+++\x1b[27m "),
+    ("filename-non-matching-left",  "bold black on brightred"),
+    ("filename-non-matching-right", "bold black on brightgreen"),
+    ("only-in-left",                "bold brightred"),
+    ("only-in-right",               "bold brightgreen"),
+    ("diff-matching-left",          "brightred"),
+    ("diff-matching-right",         "brightgreen"),
+    ("diff-non-matching-left",      "bold black on brightred"),
+    ("diff-non-matching-right",     "bold black on brightgreen"),
+    ("diff-moved-left",             "brightmagenta"),
+    ("diff-moved-right",            "brightcyan"),
+    ("diff-moved-non-matching-left",  "bold black on brightmagenta"),
+    ("diff-moved-non-matching-right", "bold black on brightcyan"),
+    ("diff-insert-left",            "\x1b[4:3:58:5:10m"),
+    ("diff-insert-right",           "\x1b[4:3;58;5;9m"),
+    ("diff-matching-inline",        "brightwhite"),
+    ("diff-context",                "white"),
+    ("diff-trailing-ws",            "+reverse"),
+    ("diff-reindent",               "bold brightyellow"),
+    ("diff-ignored-space",          "on brightblack"),
+    ("diff-invisible",              "+bold brightcyan"),
+    ("diff-bidi",                   "bold brightwhite on brightred"),
+    ("syntax-keyword",              "+brightmagenta"),
+    ("syntax-string",               "+brightyellow"),
+    ("syntax-comment",              "+cyan"),
+    ("syntax-constant",             "+brightcyan"),
+    ("syntax-type",                 "+brightblue"),
+];
+
+impl Theme {
+    // query is whether the terminal can be asked for its background colour
+    pub fn resolve(self, query: bool) -> Self {
+        if self != Self::Auto {
+            return self
+        }
+        match super::terminal::is_light_background(query) {
+            Some(true) => Self::Light,
+            _ => Self::Dark,
+        }
+    }
+
+    // the theme as command line arguments
+    pub fn args(self, query: bool) -> Vec<String> {
+        let styles = match self.resolve(query) {
+            Self::Dark | Self::Auto => DARK,
+            Self::Light => LIGHT,
+            Self::HighContrast => HIGH_CONTRAST,
+        };
+        styles.iter().map(|(option, style)| format!("--{option}={style}")).collect()
+    }
+}
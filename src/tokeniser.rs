@@ -1,4 +1,6 @@
 use std::collections::HashMap;
+use bstr::ByteSlice;
+use regex::bytes::Regex;
 use crate::types::Word;
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
@@ -17,15 +19,67 @@ impl Token {
     }
 }
 
+/// How lines are split into words.
+#[derive(Debug, Clone, Default)]
+pub enum Words {
+    /// Splits camelCase, SCREAMING_CASE, digits and runs of other characters.
+    #[default]
+    Code,
+    /// Every character is a word.
+    Chars,
+    /// Whole words, e.g. for prose or hex hashes.
+    Whole,
+    /// Anything the regex does not match is split into characters.
+    Regex(Regex),
+}
+
+// the base name and extension of a file name, which may still have quotes, a timestamp or a newline on it
+pub(crate) fn name_and_extension(filename: &[u8]) -> (&[u8], &[u8]) {
+    let filename = filename.split(|&c| c == b'\t').next().unwrap_or(filename);
+    let filename = filename.trim_ascii().strip_suffix(b"\"").unwrap_or(filename.trim_ascii());
+    let name = filename.rsplit(|&c| c == b'/').next().unwrap_or(filename);
+    let extension = name.iter().rposition(|&c| c == b'.').map(|i| &name[i+1..]).unwrap_or(b"");
+    (name, extension)
+}
+
+impl Words {
+    /// Picks the words for a file from its name, which may still have quotes, a timestamp or a newline on it.
+    pub fn from_filename(filename: &[u8]) -> Self {
+        let (name, extension) = name_and_extension(filename);
+        match (name, extension) {
+            (_, b"md" | b"markdown" | b"txt" | b"rst" | b"adoc" | b"org" | b"tex") => Self::Whole,
+            (b"README" | b"LICENSE" | b"COPYING" | b"NEWS" | b"CHANGELOG", _) => Self::Whole,
+            // mostly hashes
+            (_, b"lock" | b"sum" | b"sha1" | b"sha256" | b"sha512") => Self::Whole,
+            // identifiers may have dashes and other symbols in them, but the colon of a keyword is split off
+            (_, b"clj" | b"cljs" | b"cljc" | b"edn" | b"el" | b"lisp" | b"lsp" | b"scm" | b"ss" | b"rkt" | b"fnl") => {
+                crate::regexes::byte_regex!(r#"[^\s()\[\]{}"'`,;:]+"#, |r| { Self::Regex(r.clone()) })
+            },
+            _ => Self::Code,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Tokeniser {
     mapping: HashMap<Word, Token>,
+    // the words to use for every file, otherwise they are picked from the file name
+    pub words: Option<Words>,
+    file_words: Words,
+}
+
+impl Default for Tokeniser {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Tokeniser {
     pub fn new() -> Tokeniser {
         let mut tokeniser = Tokeniser {
-            mapping: HashMap::new()
+            mapping: HashMap::new(),
+            words: None,
+            file_words: Words::default(),
         };
         tokeniser.mapping.insert(b"\n".into(), Token::NEWLINE);
         tokeniser.mapping.insert(b" ".into(), Token::SPACE);
@@ -35,6 +89,66 @@ impl Tokeniser {
         tokeniser
     }
 
+    pub fn with_words(words: Option<Words>) -> Tokeniser {
+        Tokeniser{words, ..Self::new()}
+    }
+
+    // called for each file in the diff
+    #[cfg(feature = "cli")]
+    pub fn set_filename(&mut self, filename: &[u8]) {
+        self.file_words = Words::from_filename(filename);
+    }
+
+    // the words for the current file
+    pub fn current_words(&self) -> &Words {
+        self.words.as_ref().unwrap_or(&self.file_words)
+    }
+
+    // splits a line into words, with the newline as a word of its own
+    pub fn split<'a>(&self, line: &'a [u8]) -> Vec<&'a [u8]> {
+        let body = line.strip_suffix(b"\n").unwrap_or(line);
+        let mut words = vec![];
+
+        let mut find = |regex: &Regex| {
+            let mut end = 0;
+            for m in regex.find_iter(body).filter(|m| !m.is_empty()) {
+                words.extend(body[end..m.start()].char_indices().map(|(s, e, _)| &body[end + s .. end + e]));
+                words.push(m.as_bytes());
+                end = m.end();
+            }
+            words.extend(body[end..].char_indices().map(|(s, e, _)| &body[end + s .. end + e]));
+        };
+
+        // combining marks stay with the character before them
+        match self.current_words() {
+            Words::Code => crate::regexes::byte_regex!(
+                r"(?:"
+                r"[A-Z][A-Z_]*[A-Z]\d*"
+                r"|[A-Z][a-z0-9]*[a-z]"
+                r"|[a-z0-9]+[a-z]"
+                r"|\d+"
+                r"|\s"
+                r"|[-!=~+]="
+                r"|(?:"
+                    r"(?:[\xc0-\xdf][\x80-\xbf])"
+                    r"|(?:[\xe0-\xef][\x80-\xbf][\x80-\xbf])"
+                    r"|(?:[\xf0-\xf7][\x80-\xbf][\x80-\xbf][\x80-\xbf])"
+                r")+"
+                "|."
+                r")\p{M}*",
+                |r| { find(r) }
+            ),
+            Words::Chars => crate::regexes::byte_regex!(r"\P{M}\p{M}*", |r| { find(r) }),
+            Words::Whole => crate::regexes::byte_regex!(r"\w+|\s|\P{M}\p{M}*", |r| { find(r) }),
+            Words::Regex(regex) => find(regex),
+        }
+
+        if body.len() < line.len() {
+            words.push(&line[body.len()..]);
+        }
+        words
+    }
+
     pub fn max_token(&self) -> Token {
         Token(self.mapping.len())
     }
@@ -3,11 +3,40 @@ use std::cmp::min;
 use super::block_maker::BlockMaker;
 use super::part::Part;
 use super::tokeniser::Token;
+#[cfg(feature = "cli")]
+use super::style::IgnoreSpace;
 
 fn isjunk(tok: Token) -> bool {
     tok.is_ascii_whitespace()
 }
 
+// whether a non matching part only changes whitespace that is being ignored
+#[cfg(feature = "cli")]
+pub fn ignorable(part: &Part, ignore: IgnoreSpace) -> bool {
+    if part.matches || (part.is_empty(0) && part.is_empty(1)) {
+        return false
+    }
+    let tokens = [0, 1].map(|i| &part.parent.tokens[i][part.slices[i].clone()]);
+    if !tokens.iter().all(|t| t.iter().all(|&t| isjunk(t))) {
+        return false
+    }
+    match ignore {
+        IgnoreSpace::All => true,
+        IgnoreSpace::Change => {
+            let tokens = &part.parent.tokens;
+            let slices = &part.slices;
+            // there is still some whitespace here, even if this side has none of its own
+            let has_space = |i: usize| {
+                !part.is_empty(i)
+                || tokens[i].get(slices[i].end).is_some_and(|&t| isjunk(t))
+                || slices[i].start.checked_sub(1).is_some_and(|j| isjunk(tokens[i][j]))
+            };
+            let ends_line = |i: usize| tokens[i].get(slices[i].end).is_none_or(|&t| t == Token::NEWLINE);
+            (has_space(0) && has_space(1)) || (ends_line(0) && ends_line(1))
+        },
+    }
+}
+
 pub struct WordDiffer<'a> {
     parent: &'a BlockMaker<'a>,
 
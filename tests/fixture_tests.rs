@@ -2,16 +2,39 @@
 // use predicates::prelude::*; // Used for writing assertions
 use std::process::Command; // Run programs
 
-fn run_test(file: String) {
+fn run_test(name: String, file: String, args: &[&str]) {
+    run_env_test(name, file, args, &[])
+}
+
+fn run_env_test(name: String, file: String, args: &[&str], envs: &[(&str, &str)]) {
     let output = Command::new("bash")
         .arg("-c")
-        .arg(format!("< fixtures/input/{} cargo run -- --color=always --inline=always", file))
+        .arg(format!("< fixtures/input/{} cargo run -- --color=always --inline=always {}", file, args.join(" ")))
+        // colours depend on the terminal otherwise
+        .env("COLORTERM", "truecolor")
+        .envs(envs.iter().copied())
         .output()
         .expect("failed to execute process");
 
     assert!(!output.status.success());
 
-    let expected = std::fs::read_to_string(format!("fixtures/output/{}", file)).unwrap();
+    let expected = std::fs::read_to_string(format!("fixtures/output/{}", name)).unwrap();
+    assert_eq!(std::str::from_utf8(&output.stdout).unwrap(), expected);
+}
+
+fn run_files_test(dir: String, args: &[&str]) {
+    let output = Command::new("cargo")
+        .args(["run", "--", "--color=always", "--inline=always"])
+        .args(args)
+        .arg(format!("fixtures/input/{}/a", dir))
+        .arg(format!("fixtures/input/{}/b", dir))
+        .env("COLORTERM", "truecolor")
+        .output()
+        .expect("failed to execute process");
+
+    assert!(!output.status.success());
+
+    let expected = std::fs::read_to_string(format!("fixtures/output/{}", dir)).unwrap();
     assert_eq!(std::str::from_utf8(&output.stdout).unwrap(), expected);
 }
 
@@ -19,7 +42,22 @@ macro_rules! fixture_test {
     ($file:ident) => {
         #[test]
         fn $file() {
-            run_test(stringify!($file).to_owned())
+            run_test(stringify!($file).to_owned(), stringify!($file).to_owned(), &[])
+        }
+    };
+    ($name:ident, $file:ident $(, $arg:literal)*) => {
+        #[test]
+        fn $name() {
+            run_test(stringify!($name).to_owned(), stringify!($file).to_owned(), &[$($arg),*])
+        }
+    };
+}
+
+macro_rules! fixture_files_test {
+    ($dir:ident $(, $arg:literal)*) => {
+        #[test]
+        fn $dir() {
+            run_files_test(stringify!($dir).to_owned(), &[$($arg),*])
         }
     }
 }
@@ -62,3 +100,189 @@ fixture_test!(diff35);
 fixture_test!(diff36);
 fixture_test!(diff37);
 fixture_test!(diff38);
+fixture_test!(diff39);
+fixture_test!(diff40);
+fixture_test!(diff41);
+fixture_test!(diff42);
+fixture_test!(diff43);
+fixture_test!(diff44, diff44, "--word-mode=code");
+fixture_test!(diff45);
+fixture_test!(diff46);
+fixture_test!(diff47);
+fixture_test!(diff48);
+fixture_test!(diff49);
+fixture_test!(diff1_side_by_side, diff1, "--side-by-side", "--width=120");
+fixture_test!(diff16_side_by_side, diff16, "--side-by-side", "--width=100", "--signs");
+fixture_test!(diff39_preserve_lines, diff39, "--preserve-lines");
+fixture_test!(diff40_preserve_lines, diff40, "--preserve-lines");
+fixture_test!(diff41_color_moved, diff41, "--color-moved");
+fixture_test!(diff41_color_moved_16, diff41, "--color-moved", "--color-depth=16");
+fixture_test!(diff42_syntax, diff42, "--syntax");
+fixture_test!(diff10_syntax, diff10, "--syntax");
+fixture_test!(diff1_json, diff1, "--output=json");
+fixture_test!(diff1_html, diff1, "--output=html");
+fixture_test!(diff42_markers, diff42, "--markers");
+fixture_test!(diff1_markers, diff1, "--markers", "--marker-delete-start='<del>'", "--marker-delete-end='</del>'", "--marker-insert-start='<ins>'", "--marker-insert-end='</ins>'");
+fixture_test!(diff22_hyperlinks, diff22, "--hyperlinks", "--hyperlink-format=vscode://file{path}:{line}");
+fixture_test!(diff43_side_by_side, diff43, "--side-by-side", "--width=60");
+fixture_test!(diff44_inline_never, diff44, "--word-mode=code", "--inline=never");
+fixture_test!(diff44_side_by_side, diff44, "--word-mode=code", "--side-by-side", "--width=40");
+fixture_test!(diff44_txt_words, diff44);
+fixture_test!(diff45_chars, diff45, "--word-mode=chars");
+fixture_test!(diff46_char_diff, diff46, "--char-diff");
+fixture_test!(diff46_char_diff_inline_never, diff46, "--char-diff", "--inline=never");
+fixture_test!(diff46_char_diff_side_by_side, diff46, "--char-diff", "--side-by-side", "--width=80");
+fixture_test!(diff45_word_regex, diff45, "--word-regex='[a-z]+|\\d+'");
+fixture_test!(diff47_no_reindent, diff47, "--no-reindent");
+fixture_test!(diff47_signs, diff47, "--signs", "--no-line-numbers");
+fixture_test!(diff48_ignore_space_change, diff48, "--ignore-space-change");
+fixture_test!(diff48_ignore_all_space, diff48, "--ignore-all-space");
+fixture_test!(diff48_ignore_all_space_inline_never, diff48, "--ignore-all-space", "--inline=never");
+fixture_test!(diff49_show_invisibles, diff49, "--show-invisibles");
+fixture_test!(diff49_show_invisibles_side_by_side, diff49, "--show-invisibles", "--side-by-side", "--width=100");
+fixture_test!(diff49_color_never, diff49, "--color=never");
+fixture_test!(diff49_markers, diff49, "--markers", "--show-invisibles");
+fixture_test!(diff49_json, diff49, "--output=json");
+fixture_test!(diff1_line_number_width, diff1, "--line-number-width=6");
+fixture_test!(diff39_stat, diff39, "--stat");
+fixture_test!(diff41_summary, diff41, "--summary");
+fixture_test!(diff41_summary_color_never, diff41, "--summary", "--color=never");
+fixture_test!(diff1_light, diff1, "--theme=light");
+fixture_test!(diff16_high_contrast, diff16, "--theme=high-contrast");
+fixture_test!(diff1_profile, diff1, "--config=fixtures/config.toml", "--profile=review");
+fixture_test!(diff1_profile_negated, diff1, "--config=fixtures/config.toml", "--profile=review", "--no-signs", "--line-numbers");
+fixture_test!(diff1_16, diff1, "--color-depth=16");
+fixture_test!(diff16_256, diff16, "--color-depth=256", "--theme=light");
+fixture_test!(diff1_style_spec, diff1, "--diff-non-matching-left='bold #dcbed2 on #231414'", "--lineno='red reverse'", "--diff-insert-right=+underline:curly");
+fixture_test!(diff1_file_options, diff1, "-r", "--include='*.c'", "-x", "target");
+
+fixture_files_test!(files1);
+fixture_files_test!(dirs1, "-r", "--exclude=target");
+fixture_files_test!(dirs2, "-r");
+fixture_files_test!(files2, "--ignore-all-space", "--label=a", "--label=b");
+
+#[test]
+fn missing_file() {
+    // 1 means the files differ, so trouble has to be 2 like diff
+    let output = Command::new("cargo")
+        .args(["run", "--", "fixtures/input/missing", "fixtures/input/diff1"])
+        .output()
+        .expect("failed to execute process");
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+    let stderr = std::str::from_utf8(&output.stderr).unwrap();
+    assert!(stderr.ends_with("dyff: fixtures/input/missing: No such file or directory\n"), "{stderr}");
+}
+
+#[test]
+fn dangling_symlink() {
+    // reported like a missing file, without stopping the rest of the comparison
+    let output = Command::new("cargo")
+        .args(["run", "--", "-r", "fixtures/input/dirs3/a", "fixtures/input/dirs3/b"])
+        .output()
+        .expect("failed to execute process");
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+    let stderr = std::str::from_utf8(&output.stderr).unwrap();
+    assert!(stderr.ends_with("dyff: fixtures/input/dirs3/a/link: No such file or directory\n"), "{stderr}");
+}
+
+#[test]
+fn missing_config() {
+    // the default config file may be missing, but not one that was asked for
+    for args in [["--config=fixtures/missing.toml", "--profile=review"], ["--config=fixtures/missing.toml", "--signs"]] {
+        let output = Command::new("cargo")
+            .args(["run", "--"])
+            .args(args)
+            .args(["fixtures/input/diff1", "fixtures/input/diff2"])
+            .output()
+            .expect("failed to execute process");
+
+        assert!(output.stdout.is_empty());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("fixtures/missing.toml"));
+    }
+}
+
+#[test]
+fn failing_filter() {
+    let output = Command::new("cargo")
+        .args(["run", "--", "--filter=false", "fixtures/input/diff1", "fixtures/input/diff2"])
+        .output()
+        .expect("failed to execute process");
+
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn large_files() {
+    // lots of repeated lines like } and blank lines used to make the builtin engine quadratic
+    let dir = std::env::temp_dir().join(format!("dyff-large-files-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut left = String::new();
+    let mut right = String::new();
+    for i in 0..5000 {
+        let function = format!("fn f{i}(x: u32) -> u32 {{\n    if x > {i} {{\n        return x;\n    }}\n    x * 2\n}}\n\n");
+        left += &function;
+        right += &if i % 100 == 0 { function.replace("x * 2", "x * 3") } else { function };
+    }
+    std::fs::write(dir.join("a"), left).unwrap();
+    std::fs::write(dir.join("b"), right).unwrap();
+
+    let start = std::time::Instant::now();
+    let output = Command::new("cargo")
+        .args(["run", "--", "--color=always", "-U0"])
+        .arg(dir.join("a"))
+        .arg(dir.join("b"))
+        .output()
+        .expect("failed to execute process");
+    let elapsed = start.elapsed();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(!output.status.success());
+    let hunks = output.stdout.split(|&c| c == b'\n').filter(|l| l.starts_with(b"\x1b[0;36m@@")).count();
+    assert_eq!(hunks, 50);
+    assert!(elapsed.as_secs() < 30, "took {:?}", elapsed);
+}
+
+#[test]
+fn diff1_git_config() {
+    run_env_test("diff1_git_config".to_owned(), "diff1".to_owned(), &[], &[
+        ("GIT_CONFIG_COUNT", "3"),
+        ("GIT_CONFIG_KEY_0", "dyff.signs"),
+        ("GIT_CONFIG_VALUE_0", "true"),
+        ("GIT_CONFIG_KEY_1", "dyff.diffNonMatchingLeft"),
+        ("GIT_CONFIG_VALUE_1", "bold blue"),
+        ("GIT_CONFIG_KEY_2", "dyff.no-line-numbers"),
+        ("GIT_CONFIG_VALUE_2", "yes"),
+    ])
+}
+
+#[test]
+fn diff1_git_config_negated() {
+    // git config turns off what the config file turns on, and skips what it doesn't know
+    run_env_test("diff1".to_owned(), "diff1".to_owned(), &["--config=fixtures/config.toml"], &[
+        ("GIT_CONFIG_COUNT", "2"),
+        ("GIT_CONFIG_KEY_0", "dyff.signs"),
+        ("GIT_CONFIG_VALUE_0", "false"),
+        ("GIT_CONFIG_KEY_1", "dyff.someFutureOption"),
+        ("GIT_CONFIG_VALUE_1", "true"),
+    ])
+}
+
+#[test]
+fn diff1_theme_auto() {
+    // piped output never asks the terminal, so the theme comes from COLORFGBG
+    run_env_test("diff1_light".to_owned(), "diff1".to_owned(), &["--theme=auto"], &[
+        ("COLORFGBG", "0;15"),
+    ])
+}
+
+#[test]
+fn diff1_256() {
+    run_env_test("diff1_256".to_owned(), "diff1".to_owned(), &[], &[
+        ("COLORTERM", ""),
+        ("TERM", "xterm-256color"),
+    ])
+}